@@ -1,13 +1,30 @@
-use anyhow::Result;
+use crate::backend::TmuxBackend;
+use crate::config_file::{self, LocalConfig};
+use crate::describe::OutputFormat;
+use crate::import::ImportFormat;
+use crate::inherit;
+use crate::layout::{PaneTree, WindowLayout};
+use crate::rules;
+use crate::tmux::{Pane, Window};
+use crate::trust;
+use anyhow::{Context, Result};
 use clap::{crate_authors, crate_description, crate_name, crate_version, Arg};
 
 use std::fs::canonicalize;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 // const DEFAULT_LAYOUT: &str = "34ed,230x56,0,0{132x56,0,0,3,97x56,133,0,222}";
 
 fn args() -> clap::ArgMatches {
+    args_from(std::env::args_os())
+}
+
+fn args_from<I, T>(args: I) -> clap::ArgMatches
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString> + Clone,
+{
     let fzf_available = Command::new("fzf")
         .arg("--version")
         .stdout(Stdio::null())
@@ -18,6 +35,8 @@ fn args() -> clap::ArgMatches {
         .version(crate_version!())
         .author(crate_authors!())
         .about(crate_description!())
+        // subcommands don't open a directory, so they never need one
+        .subcommand_negates_reqs(true)
         .arg(
             Arg::new("selected_dir")
                 .help("Open this directory directly without starting a selector")
@@ -56,12 +75,31 @@ fn args() -> clap::ArgMatches {
                 .takes_value(true),
         )
         .arg(
-            // We should use validator here
             Arg::new("layout")
                 .short('l')
                 .long("layout")
                 .help("specify the window layout (layouts are dependent on the number of panes)")
                 .long_help(layout_long_help().as_str())
+                .validator(|layout| layout.parse::<WindowLayout>())
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("main_pane_width")
+                .long("main-pane-width")
+                .help("width of the main pane in the main-vertical layouts, in cells or as a percentage")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("main_pane_height")
+                .long("main-pane-height")
+                .help("height of the main pane in the main-horizontal layouts, in cells or as a percentage")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("layout_policy")
+                .long("layout-policy")
+                .help("what to do when the layout doesn't describe as many panes as the workspace has")
+                .possible_values(["strict", "grow_panes", "ignore"])
                 .takes_value(true),
         )
         .arg(
@@ -69,6 +107,44 @@ fn args() -> clap::ArgMatches {
                 .short('P')
                 .long("profile")
                 .help("Use a different configuration profile.")
+                .global(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("dry_run")
+                .long("dry-run")
+                .help("print the tmux commands that would set up the workspace instead of running them"),
+        )
+        .arg(
+            Arg::new("explain")
+                .long("explain")
+                .help("print which of the config's rules picked the profile for the directory"),
+        )
+        .arg(
+            Arg::new("list_running")
+                .long("list-running")
+                .help("list the windows dmux opened before the dirs in the picker, picking one switches to it"),
+        )
+        .arg(
+            Arg::new("no_batch")
+                .long("no-batch")
+                .help("run the tmux commands one at a time instead of in a single call, to find which one fails"),
+        )
+        .arg(
+            Arg::new("socket_name")
+                .short('L')
+                .long("socket-name")
+                .help("talk to the tmux server with this socket name, like `tmux -L`")
+                .conflicts_with("socket_path")
+                .global(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("socket_path")
+                .short('S')
+                .long("socket-path")
+                .help("talk to the tmux server at this socket path, like `tmux -S`")
+                .global(true)
                 .takes_value(true),
         )
         .arg(
@@ -95,14 +171,176 @@ fn args() -> clap::ArgMatches {
                 ),
         )
         .subcommand(
-            clap::Command::new("layout").about("generates the current layout string from tmux"),
+            clap::Command::new("layout")
+                .about("generates the current layout string from tmux")
+                .arg(
+                    Arg::new("session")
+                        .long("session")
+                        .help("describe the active window of this session instead of the current one")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("window")
+                        .long("window")
+                        .help("describe the window with this name or index")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .help("describe every window, of the session if one is given, otherwise of every session"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .help("raw prints just the layout string, the other formats also describe every pane and give a profile for the window")
+                        .possible_values(["raw", "json", "toml", "yaml"])
+                        .default_value("raw")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("export")
+                .about("prints a shell script that opens the workspace with plain tmux calls, for people without dmux")
+                .arg(
+                    Arg::new("dir")
+                        .help("the directory the workspace opens in, the script can be given another one as its first argument")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("switch")
+                .about("picks a running dmux window to switch to, or a dir to open a workspace in"),
+        )
+        .subcommand(
+            clap::Command::new("list")
+                .visible_alias("ls")
+                .about("lists the sessions and windows dmux opened")
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .help("print the sessions and their windows as JSON"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("kill")
+                .about("kills dmux sessions or windows, picked with fzf unless they're named")
+                .arg(
+                    Arg::new("targets")
+                        .help("sessions or session:window targets to kill instead of picking them")
+                        .multiple_values(true),
+                )
+                .arg(
+                    Arg::new("session")
+                        .long("session")
+                        .help("only list sessions")
+                        .conflicts_with("window"),
+                )
+                .arg(
+                    Arg::new("window")
+                        .long("window")
+                        .help("only list windows"),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .short('y')
+                        .long("yes")
+                        .help("kill without asking first"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("import")
+                .about("adds a tmuxinator or tmuxp project file to the config file as a profile")
+                .arg(
+                    Arg::new("format")
+                        .help("the tool the project file is for")
+                        .possible_values(["tmuxinator", "tmuxp"])
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("file")
+                        .help("the project file to import")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("name")
+                        .long("name")
+                        .help("name of the new profile, defaults to the project's name")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("save")
+                .about("saves a session's windows, layouts and pane commands to a snapshot file")
+                .arg(
+                    Arg::new("session")
+                        .help("the session to save, defaults to the current one"),
+                )
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .help("save every session dmux opened a window in")
+                        .conflicts_with("session"),
+                ),
         )
-        .get_matches()
+        .subcommand(
+            clap::Command::new("restore")
+                .about("opens a saved session again, picked with fzf unless it's named")
+                .arg(
+                    Arg::new("name")
+                        .help("the snapshot to restore, named after its session"),
+                )
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .help("restore every saved session")
+                        .conflicts_with("name"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("trust")
+                .about("shows the commands in a directory's dmux.local file and trusts it, which dmux needs before using it")
+                .arg(
+                    Arg::new("dir")
+                        .help("the directory with the dmux.local file")
+                        .default_value("."),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .short('y')
+                        .long("yes")
+                        .help("trust the file without asking first"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("freeze")
+                .about("saves the current window's layout and commands as a new profile in the config file")
+                .arg(
+                    Arg::new("name")
+                        .help("name of the new profile, defaults to the window name"),
+                )
+                .arg(
+                    Arg::new("session")
+                        .long("session")
+                        .help("freeze the active window of this session instead of the current one")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("window")
+                        .long("window")
+                        .help("freeze the window with this name or index")
+                        .takes_value(true),
+                ),
+        )
+        .get_matches_from(args)
 }
 
 fn layout_long_help() -> String {
     format!(
-        "This string is the same representation that
+        "Either the name of one of tmux's preset layouts
+(even-horizontal, even-vertical, main-horizontal,
+main-vertical, tiled), or a layout string.
+This string is the same representation that
 tmux itself uses to setup it's own layouts.
 Use `{} layout` to generate the layout string
 for the current tmux configuration. This is
@@ -125,8 +363,11 @@ is a list of commands. These commands will
 be run in the panes of the tmux window that
 will be opened by {:?}. The commands index 
 (beginning with 0) corresponds to the pane
-id. Pane id's can be found easily with 
-`<prefix >q` in tmux.
+index. Pane indexes can be found easily with 
+`<prefix> q` in tmux. In the config file a
+command can also be a table with `command`,
+`dir` (relative to the opened directory),
+`env`, `title`, `focus = true` and `zoom = true`.
  ",
         crate_name!()
     )
@@ -140,6 +381,10 @@ fn default_layout_checksum() -> String {
     "34ed,230x56,0,0{132x56,0,0,3,97x56,133,0,222}".to_string()
 }
 
+fn default_layout() -> LayoutArg {
+    LayoutArg::Raw(default_layout_checksum())
+}
+
 fn default_session_name() -> String {
     "dev".to_string()
 }
@@ -148,57 +393,98 @@ fn default_number_of_panes() -> u8 {
     2
 }
 
-fn default_commands() -> Vec<String> {
-    vec!["vim".to_string(), "ls".to_string()]
+const DEFAULT_COMMANDS: [&str; 2] = ["vim", "ls"];
+
+fn default_commands() -> Vec<Pane> {
+    panes_from(DEFAULT_COMMANDS.iter().map(|command| command.to_string()))
+}
+
+fn panes_from(commands: impl IntoIterator<Item = String>) -> Vec<Pane> {
+    commands.into_iter().map(Pane::from).collect()
 }
 
 fn default_window_name() -> Option<String> {
     None
 }
 
+fn default_layout_policy() -> LayoutPolicy {
+    LayoutPolicy::Ignore
+}
+
 fn config_file_settings() -> Result<config::Config> {
     // switch to confy perobably
     let default = WorkSpaceArgs::default();
     let mut settings = config::Config::default();
-    let mut config_conf =
-        dirs::config_dir().ok_or_else(|| anyhow!("Config dir couldn't be read"))?;
-    config_conf.push("dmux/dmux.conf.xxxx");
-
-    let mut home_conf =
-        dirs::home_dir().ok_or_else(|| anyhow!("Home directory couldn't be found"))?;
-    home_conf.push(".dmux.conf.xxxx");
-
-    let mut mac_config =
-        dirs::home_dir().ok_or_else(|| anyhow!("Home directory couldn't be found"))?;
-    mac_config.push(".config/dmux/dmux.conf.xxx");
+    for base in config_file::config_file_bases()? {
+        // `config` swaps this extension out for the extension of every format it supports
+        let name = format!(
+            "{}.xxxx",
+            base.to_str()
+                .ok_or_else(|| anyhow!("Config path {:?} isn't valid unicode", base))?
+        );
+        settings.merge(config::File::with_name(&name).required(false))?;
+    }
     Ok(settings
-        // ~/dmux.conf.(yaml | json | toml)
-        .merge(config::File::with_name(config_conf.to_str().unwrap()).required(false))?
-        // ~/{xdg_config|.config}dmux.conf.(yaml | json | toml)
-        .merge(config::File::with_name(home_conf.to_str().unwrap()).required(false))?
-        .merge(config::File::with_name(mac_config.to_str().unwrap()).required(false))?
         // Add in settings from the environment (with a prefix of DMUX)
         // Eg.. `DMUX_SESSION_NAME=foo dmux` would set the `session_name` key
         .merge(config::Environment::with_prefix("DMUX"))?
-        .set_default("layout", default.layout)?
+        .set_default("layout", default_layout_checksum())?
         // the trait `std::convert::From<i32>` is not implemented for `config::value::ValueKind`
         .set_default("number_of_panes", default.number_of_panes as i64)?
-        .set_default("commands", default.commands)?
+        .set_default("commands", DEFAULT_COMMANDS.to_vec())?
         .set_default("session_name", default.session_name)?
         .to_owned())
 }
 
-fn settings_config(settings: config::Config, target: Option<&str>) -> Result<WorkSpaceArgs> {
-    if let Some(target) = target {
-        let profile: WorkSpaceArgs = settings.get(target)?;
-        return Ok(profile);
+// The settings of the profile, with the directory's local config merged over them
+fn settings_config(
+    settings: config::Config,
+    target: Option<&str>,
+    local: Option<LocalConfig>,
+) -> Result<WorkSpaceArgs> {
+    let mut settings = match target {
+        Some(target) => {
+            let table = inherit::resolve_profile(&settings, target)?;
+            let mut profile = config::Config::default();
+            for (key, value) in table {
+                // defaults, so the local config's keys take their place
+                profile.set_default(&key, value)?;
+            }
+            profile
+        }
+        None => settings,
+    };
+    if let Some(local) = local {
+        settings
+            .merge(config::File::from_str(
+                &local.contents,
+                local.format.file_format(),
+            ))
+            .with_context(|| format!("Couldn't read {:?}", local.path))?;
     }
     let profile: WorkSpaceArgs = settings.try_into()?;
     Ok(profile)
 }
 
+// The parsed command line, kept so the workspace args can be worked out
+// again once the directory is known, as it can have a config of its own
+#[derive(Debug)]
+pub struct CommandLine {
+    args: clap::ArgMatches,
+}
+
+impl CommandLine {
+    pub fn open_args(&self, selected_dir: PathBuf) -> Result<OpenArgs> {
+        Ok(OpenArgs {
+            workspace: build_workspace_args(&self.args, Some(&selected_dir))?,
+            selected_dir,
+        })
+    }
+}
+
 pub struct SelectArgs {
     pub workspace: WorkSpaceArgs,
+    pub command_line: CommandLine,
 }
 
 pub enum CommandType {
@@ -209,49 +495,283 @@ pub enum CommandType {
     // Pull a repo from a git repository and then open that dir
     Pull(PullArgs),
     // Generate a tmux layout for the setup of panes in the current window
-    Layout,
+    Layout(LayoutArgs),
+    // Save the current window as a profile in the config file
+    Freeze(FreezeArgs),
+    // Print a shell script that opens the workspace without dmux
+    Export(OpenArgs),
+    // Add a tmuxinator or tmuxp project to the config file as a profile
+    Import(ImportArgs),
+    // Kill sessions or windows dmux opened
+    Kill(KillArgs),
+    // List the sessions and windows dmux opened
+    List(ListArgs),
+    // Save sessions to snapshot files
+    Save(SaveArgs),
+    // Open sessions again from their snapshots
+    Restore(RestoreArgs),
+    // Approve a directory's dmux.local file
+    Trust(TrustArgs),
+}
+
+// Decides what happens when the number of panes in the layout
+// doesn't match the number of panes in the workspace
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutPolicy {
+    // Refuse to open the workspace
+    Strict,
+    // Add empty panes until the workspace matches the layout
+    GrowPanes,
+    // Hand the layout to tmux anyway
+    Ignore,
+}
+
+impl std::str::FromStr for LayoutPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(policy: &str) -> Result<Self> {
+        match policy {
+            "strict" => Ok(LayoutPolicy::Strict),
+            "grow_panes" => Ok(LayoutPolicy::GrowPanes),
+            "ignore" => Ok(LayoutPolicy::Ignore),
+            _ => Err(anyhow!("unknown layout policy {:?}", policy)),
+        }
+    }
+}
+
+// A layout is either a tmux layout string or a tree of panes
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum LayoutArg {
+    Raw(String),
+    Tree(PaneTree),
+}
+
+impl LayoutArg {
+    pub fn to_layout(&self) -> Result<WindowLayout> {
+        match self {
+            LayoutArg::Raw(layout) => layout.parse(),
+            LayoutArg::Tree(tree) => Ok(WindowLayout::Custom(tree.compile()?)),
+        }
+    }
+}
+
+// One window of a workspace with several windows, anything
+// left out falls back to the layout settings of the profile
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct WindowArgs {
+    pub name: Option<String>,
+    pub layout: Option<LayoutArg>,
+    pub number_of_panes: Option<u8>,
+    pub commands: Option<Vec<Pane>>,
+    pub main_pane_width: Option<String>,
+    pub main_pane_height: Option<String>,
 }
 
 // I don't like the repetition here
 #[derive(Deserialize, Debug)]
 pub struct WorkSpaceArgs {
-    #[serde(default = "default_layout_checksum")]
-    pub layout: String,
+    #[serde(default = "default_layout")]
+    pub layout: LayoutArg,
     #[serde(default = "default_session_name")]
     pub session_name: String,
     #[serde(default = "default_number_of_panes")]
     pub number_of_panes: u8,
     #[serde(default = "default_search_dir")]
     pub search_dir: PathBuf,
+    // whether the picker lists running windows before the dirs
+    #[serde(default)]
+    pub list_running: bool,
     #[serde(default = "default_commands")]
-    pub commands: Vec<String>,
+    pub commands: Vec<Pane>,
     #[serde(default = "default_window_name")]
     pub window_name: Option<String>,
+    #[serde(default)]
+    pub main_pane_width: Option<String>,
+    #[serde(default)]
+    pub main_pane_height: Option<String>,
+    #[serde(default = "default_layout_policy")]
+    pub layout_policy: LayoutPolicy,
+    #[serde(default)]
+    pub windows: Vec<WindowArgs>,
+    #[serde(default)]
+    pub start_window: Option<String>,
+    #[serde(default)]
+    pub socket_name: Option<String>,
+    #[serde(default)]
+    pub socket_path: Option<PathBuf>,
+    // name of the profile these args were read from
+    #[serde(skip)]
+    pub profile: Option<String>,
+    #[serde(skip)]
+    pub dry_run: bool,
+    #[serde(skip)]
+    pub no_batch: bool,
 }
 
 impl Default for WorkSpaceArgs {
     fn default() -> Self {
         Self {
             window_name: None,
-            layout: default_layout_checksum(),
+            layout: default_layout(),
             session_name: default_session_name(),
             number_of_panes: default_number_of_panes(),
             search_dir: dirs::home_dir().unwrap(),
+            list_running: false,
             commands: default_commands(),
+            main_pane_width: None,
+            main_pane_height: None,
+            layout_policy: default_layout_policy(),
+            windows: vec![],
+            start_window: None,
+            socket_name: None,
+            socket_path: None,
+            profile: None,
+            dry_run: false,
+            no_batch: false,
         }
     }
 }
 
+impl WorkSpaceArgs {
+    pub fn profile_name(&self) -> &str {
+        self.profile.as_deref().unwrap_or("default")
+    }
+
+    pub fn tmux_backend(&self) -> TmuxBackend {
+        let mut tmux = TmuxBackend::new(self.socket_name.clone(), self.socket_path.clone());
+        tmux.batch = !self.no_batch;
+        tmux
+    }
+
+    // The windows to open, which is a single window made from the
+    // top level settings when the profile doesn't list any windows
+    pub fn to_windows(&self) -> Result<Vec<Window>> {
+        if self.windows.is_empty() {
+            let mut window = Window {
+                name: self.window_name.clone(),
+                layout: self.layout.to_layout().context("Invalid layout")?,
+                panes: self.commands.clone(),
+                number_of_panes: self.number_of_panes,
+                main_pane_width: self.main_pane_width.clone(),
+                main_pane_height: self.main_pane_height.clone(),
+            };
+            window
+                .apply_layout_policy(self.layout_policy)
+                .context("The layout doesn't fit its panes")?;
+            return Ok(vec![window]);
+        }
+
+        self.windows
+            .iter()
+            .enumerate()
+            .map(|(i, args)| {
+                let name = args.name.clone().unwrap_or_else(|| format!("#{}", i));
+                let layout_arg = args.layout.as_ref().unwrap_or(&self.layout);
+                let layout = layout_arg
+                    .to_layout()
+                    .with_context(|| format!("Invalid layout for window {}", name))?;
+                let panes = match (&args.commands, layout_arg) {
+                    (Some(commands), _) => commands.clone(),
+                    (None, LayoutArg::Tree(tree)) => panes_from(tree.commands()),
+                    (None, LayoutArg::Raw(_)) => vec![],
+                };
+                let mut window = Window {
+                    // windows without a number of panes get one for each pane in their layout
+                    number_of_panes: args
+                        .number_of_panes
+                        .or_else(|| layout.pane_count().map(|panes| panes as u8))
+                        .unwrap_or(1),
+                    name: args.name.clone(),
+                    layout,
+                    panes,
+                    main_pane_width: args
+                        .main_pane_width
+                        .clone()
+                        .or_else(|| self.main_pane_width.clone()),
+                    main_pane_height: args
+                        .main_pane_height
+                        .clone()
+                        .or_else(|| self.main_pane_height.clone()),
+                };
+                window
+                    .apply_layout_policy(self.layout_policy)
+                    .with_context(|| {
+                        format!("The layout of window {} doesn't fit its panes", name)
+                    })?;
+                Ok(window)
+            })
+            .collect()
+    }
+}
+
 pub struct OpenArgs {
     pub workspace: WorkSpaceArgs,
     pub selected_dir: PathBuf,
 }
 
+pub struct LayoutArgs {
+    pub session: Option<String>,
+    // window name or index
+    pub window: Option<String>,
+    pub all: bool,
+    pub format: OutputFormat,
+}
+
+pub struct FreezeArgs {
+    // defaults to the window name
+    pub name: Option<String>,
+    pub session: Option<String>,
+    pub window: Option<String>,
+}
+
+pub struct ListArgs {
+    pub json: bool,
+}
+
+pub struct KillArgs {
+    // picked with the selector when none are given
+    pub targets: Vec<String>,
+    pub sessions_only: bool,
+    pub windows_only: bool,
+    // skips asking before killing
+    pub yes: bool,
+}
+
+pub struct SaveArgs {
+    // defaults to the current session
+    pub session: Option<String>,
+    // every session with a window dmux opened
+    pub all: bool,
+}
+
+pub struct RestoreArgs {
+    // picked with the selector when not given
+    pub name: Option<String>,
+    pub all: bool,
+    pub dry_run: bool,
+}
+
+pub struct TrustArgs {
+    pub dir: PathBuf,
+    // skips asking before trusting
+    pub yes: bool,
+}
+
+pub struct ImportArgs {
+    pub format: ImportFormat,
+    pub file: PathBuf,
+    // defaults to the project's name
+    pub name: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct PullArgs {
     pub repo_url: String,
     pub target_dir: PathBuf,
     pub workspace: WorkSpaceArgs,
+    pub command_line: CommandLine,
 }
 
 fn read_line_iter() -> Result<String> {
@@ -274,50 +794,140 @@ fn select_dir(args: &clap::ArgMatches) -> Option<PathBuf> {
     }
 }
 
-fn build_workspace_args(args: &clap::ArgMatches) -> Result<WorkSpaceArgs> {
-    let settings = config_file_settings()?;
-    let conf_from_settings = settings_config(settings, args.value_of("profile"))?;
+// The workspace args for the directory being opened, when it's known yet
+fn build_workspace_args(args: &clap::ArgMatches, dir: Option<&Path>) -> Result<WorkSpaceArgs> {
+    workspace_args_from(config_file_settings()?, args, dir)
+}
+
+fn workspace_args_from(
+    settings: config::Config,
+    args: &clap::ArgMatches,
+    dir: Option<&Path>,
+) -> Result<WorkSpaceArgs> {
+    // without --profile, the rules pick one once the directory is known
+    let (profile, local) = match dir {
+        Some(dir) => {
+            let rules = rules::read_rules(&settings)?;
+            let choice = rules::choose_profile(args.value_of("profile"), &rules, dir)?;
+            if args.is_present("explain") {
+                eprintln!("{}", choice.explanation(dir));
+            }
+            (
+                choice.profile().map(String::from),
+                trust::trusted_local_config(dir)?,
+            )
+        }
+        None => (args.value_of("profile").map(String::from), None),
+    };
+    let conf_from_settings = settings_config(settings, profile.as_deref(), local)?;
     let search_dir = args
         .value_of_t::<PathBuf>("search_dir")
         .unwrap_or(conf_from_settings.search_dir);
+    let layout = args
+        .value_of_t::<String>("layout")
+        .map(LayoutArg::Raw)
+        .unwrap_or(conf_from_settings.layout);
+    // a pane tree can carry the commands and pane count itself
+    let (tree_commands, tree_panes) = match &layout {
+        LayoutArg::Tree(tree) => {
+            let commands = tree.commands();
+            let panes = commands.len() as u8;
+            (
+                Some(panes_from(commands)).filter(|c| c.iter().any(|c| !c.command.is_empty())),
+                Some(panes),
+            )
+        }
+        LayoutArg::Raw(_) => (None, None),
+    };
+    let (socket_name, socket_path) = choose_socket(
+        (
+            args.value_of_t::<String>("socket_name").ok(),
+            args.value_of_t::<PathBuf>("socket_path").ok(),
+        ),
+        (
+            conf_from_settings.socket_name,
+            conf_from_settings.socket_path,
+        ),
+    )?;
     Ok(WorkSpaceArgs {
         window_name: args.value_of_t::<String>("window_name").ok(),
         session_name: args
             .value_of_t::<String>("session_name")
             .unwrap_or(conf_from_settings.session_name),
-        layout: args
-            .value_of_t::<String>("layout")
-            .unwrap_or(conf_from_settings.layout),
+        layout,
         number_of_panes: args
             .value_of_t::<u8>("number_of_panes")
+            .ok()
+            .or(tree_panes)
             .unwrap_or(conf_from_settings.number_of_panes),
         commands: args
             .values_of_t::<String>("commands")
+            .ok()
+            .map(panes_from)
+            .or(tree_commands)
             .unwrap_or(conf_from_settings.commands),
+        main_pane_width: args
+            .value_of_t::<String>("main_pane_width")
+            .ok()
+            .or(conf_from_settings.main_pane_width),
+        main_pane_height: args
+            .value_of_t::<String>("main_pane_height")
+            .ok()
+            .or(conf_from_settings.main_pane_height),
+        layout_policy: args
+            .value_of_t::<LayoutPolicy>("layout_policy")
+            .unwrap_or(conf_from_settings.layout_policy),
+        profile,
+        dry_run: args.is_present("dry_run"),
+        no_batch: args.is_present("no_batch"),
+        windows: conf_from_settings.windows,
+        start_window: conf_from_settings.start_window,
+        socket_name,
+        socket_path,
         search_dir,
+        list_running: args.is_present("list_running") || conf_from_settings.list_running,
     })
 }
 
+type Socket = (Option<String>, Option<PathBuf>);
+
+// The socket name or path to talk to tmux on. One given on the command line replaces
+// the config's one, whichever kind it is, and the config can't set both either
+fn choose_socket(command_line: Socket, config: Socket) -> Result<Socket> {
+    match (command_line, config) {
+        ((None, None), (Some(_), Some(_))) => Err(anyhow!(
+            "socket_name and socket_path can't both be set in the config, tmux only uses one of them"
+        )),
+        ((None, None), config) => Ok(config),
+        (command_line, _) => Ok(command_line),
+    }
+}
+
 fn expand_selected_dir(path: PathBuf) -> Result<PathBuf> {
-    if path == PathBuf::from(".") {
+    if path == Path::new(".") {
         Ok(std::env::current_dir()?)
     } else {
         Ok(path)
     }
 }
 
-pub fn build_app() -> Result<CommandType> {
+// The command to run and the tmux server to run it against
+pub fn build_app() -> Result<(CommandType, TmuxBackend)> {
     let args = args();
-    let workspace = build_workspace_args(&args)?;
-    match args.subcommand_name() {
+    let workspace = build_workspace_args(&args, None)?;
+    let tmux = workspace.tmux_backend();
+    let command_line = CommandLine { args: args.clone() };
+    let command = match args.subcommand_name() {
         None => {
             if let Some(selected_dir) = select_dir(&args) {
-                Ok(CommandType::Open(OpenArgs {
+                Ok(CommandType::Open(command_line.open_args(
+                    expand_selected_dir(canonicalize(selected_dir)?)?,
+                )?))
+            } else {
+                Ok(CommandType::Select(SelectArgs {
                     workspace,
-                    selected_dir: expand_selected_dir(canonicalize(selected_dir)?)?,
+                    command_line,
                 }))
-            } else {
-                Ok(CommandType::Select(SelectArgs { workspace }))
             }
         }
         Some("clone") => {
@@ -333,10 +943,191 @@ pub fn build_app() -> Result<CommandType> {
                     .value_of_t::<PathBuf>("target_dir")
                     .unwrap_or_else(|_| dirs::home_dir().unwrap()),
                 workspace,
+                command_line,
             }))
         }
 
-        Some("layout") => Ok(CommandType::Layout),
+        Some("layout") => {
+            let layout_args = args
+                .subcommand_matches("layout")
+                .ok_or_else(|| anyhow!("Problem reading layout args"))?;
+            Ok(CommandType::Layout(LayoutArgs {
+                session: layout_args.value_of("session").map(String::from),
+                window: layout_args.value_of("window").map(String::from),
+                all: layout_args.is_present("all"),
+                format: layout_args.value_of_t::<OutputFormat>("format")?,
+            }))
+        }
+        Some("export") => {
+            let export_args = args
+                .subcommand_matches("export")
+                .ok_or_else(|| anyhow!("Problem reading export args"))?;
+            let dir = export_args
+                .value_of_t::<PathBuf>("dir")
+                .map_err(|_| anyhow!("No directory given, which one should I export?"))?;
+            Ok(CommandType::Export(command_line.open_args(
+                expand_selected_dir(
+                    canonicalize(&dir).with_context(|| format!("{:?} isn't a valid path", dir))?,
+                )?,
+            )?))
+        }
+        Some("freeze") => {
+            let freeze_args = args
+                .subcommand_matches("freeze")
+                .ok_or_else(|| anyhow!("Problem reading freeze args"))?;
+            Ok(CommandType::Freeze(FreezeArgs {
+                name: freeze_args.value_of("name").map(String::from),
+                session: freeze_args.value_of("session").map(String::from),
+                window: freeze_args.value_of("window").map(String::from),
+            }))
+        }
+        Some("switch") => {
+            let mut workspace = workspace;
+            workspace.list_running = true;
+            Ok(CommandType::Select(SelectArgs {
+                workspace,
+                command_line,
+            }))
+        }
+        Some("list") => {
+            let list_args = args
+                .subcommand_matches("list")
+                .ok_or_else(|| anyhow!("Problem reading list args"))?;
+            Ok(CommandType::List(ListArgs {
+                json: list_args.is_present("json"),
+            }))
+        }
+        Some("kill") => {
+            let kill_args = args
+                .subcommand_matches("kill")
+                .ok_or_else(|| anyhow!("Problem reading kill args"))?;
+            Ok(CommandType::Kill(KillArgs {
+                targets: kill_args
+                    .values_of("targets")
+                    .map(|targets| targets.map(String::from).collect())
+                    .unwrap_or_default(),
+                sessions_only: kill_args.is_present("session"),
+                windows_only: kill_args.is_present("window"),
+                yes: kill_args.is_present("yes"),
+            }))
+        }
+        Some("import") => {
+            let import_args = args
+                .subcommand_matches("import")
+                .ok_or_else(|| anyhow!("Problem reading import args"))?;
+            Ok(CommandType::Import(ImportArgs {
+                format: import_args.value_of_t::<ImportFormat>("format")?,
+                file: import_args.value_of_t::<PathBuf>("file")?,
+                name: import_args.value_of("name").map(String::from),
+            }))
+        }
+        Some("save") => {
+            let save_args = args
+                .subcommand_matches("save")
+                .ok_or_else(|| anyhow!("Problem reading save args"))?;
+            Ok(CommandType::Save(SaveArgs {
+                session: save_args.value_of("session").map(String::from),
+                all: save_args.is_present("all"),
+            }))
+        }
+        Some("restore") => {
+            let restore_args = args
+                .subcommand_matches("restore")
+                .ok_or_else(|| anyhow!("Problem reading restore args"))?;
+            Ok(CommandType::Restore(RestoreArgs {
+                name: restore_args.value_of("name").map(String::from),
+                all: restore_args.is_present("all"),
+                dry_run: workspace.dry_run,
+            }))
+        }
+        Some("trust") => {
+            let trust_args = args
+                .subcommand_matches("trust")
+                .ok_or_else(|| anyhow!("Problem reading trust args"))?;
+            Ok(CommandType::Trust(TrustArgs {
+                dir: trust_args.value_of_t::<PathBuf>("dir")?,
+                yes: trust_args.is_present("yes"),
+            }))
+        }
         Some(_) => Err(anyhow!("unexpected subcommand")),
+    }?;
+    Ok((command, tmux))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_file::ConfigFormat;
+
+    fn settings(contents: &str) -> config::Config {
+        let mut settings = config::Config::default();
+        settings
+            .merge(config::File::from_str(contents, config::FileFormat::Toml))
+            .unwrap();
+        settings
+    }
+
+    fn local(contents: &str) -> LocalConfig {
+        LocalConfig {
+            path: PathBuf::from("/srv/web/dmux.local.toml"),
+            format: ConfigFormat::Toml,
+            contents: contents.to_owned(),
+        }
+    }
+
+    #[test]
+    fn local_config_is_merged_over_the_profile() {
+        let config = "session_name = \"top\"\n\
+                      [js]\n\
+                      session_name = \"js\"\n\
+                      number_of_panes = 3\n\
+                      commands = [\"nvim\"]\n";
+        let args = settings_config(
+            settings(config),
+            Some("js"),
+            Some(local("commands = [\"npm run dev\"]")),
+        )
+        .unwrap();
+        assert_eq!(args.session_name, "js");
+        assert_eq!(args.number_of_panes, 3);
+        assert_eq!(args.commands, vec![Pane::from("npm run dev".to_owned())]);
+
+        let args =
+            settings_config(settings(config), None, Some(local("number_of_panes = 4"))).unwrap();
+        assert_eq!(args.session_name, "top");
+        assert_eq!(args.number_of_panes, 4);
+    }
+
+    #[test]
+    fn sockets_on_the_command_line_replace_the_configs() {
+        let name = || Some("pair".to_owned());
+        let path = || Some(PathBuf::from("/tmp/pair.sock"));
+        assert_eq!(
+            choose_socket((None, path()), (name(), None)).unwrap(),
+            (None, path())
+        );
+        assert_eq!(
+            choose_socket((None, None), (name(), None)).unwrap(),
+            (name(), None)
+        );
+        assert_eq!(
+            choose_socket((name(), None), (name(), path())).unwrap(),
+            (name(), None)
+        );
+        assert!(choose_socket((None, None), (name(), path())).is_err());
+    }
+
+    #[test]
+    fn rule_picked_profiles_choose_the_tmux_server() {
+        let config = "[[rules]]\n\
+                      dir = \"/srv/**\"\n\
+                      profile = \"ops\"\n\
+                      [ops]\n\
+                      socket_name = \"ops\"\n";
+        let matches = args_from(["dmux", "/srv/infra"]);
+        let workspace =
+            workspace_args_from(settings(config), &matches, Some(Path::new("/srv/infra"))).unwrap();
+        assert_eq!(workspace.profile_name(), "ops");
+        assert_eq!(workspace.tmux_backend().server_args(), vec!["-L", "ops"]);
     }
 }
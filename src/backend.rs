@@ -0,0 +1,906 @@
+// The tmux operations dmux uses, behind a trait so that setting up a
+// workspace can be tested without a tmux server
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use tmux_interface::{TmuxCommand, TmuxOutput};
+
+use crate::tmux::{PaneInfo, WindowInfo};
+
+pub trait Backend {
+    // whether dmux is running in a client of this server
+    fn in_tmux(&mut self) -> bool;
+    fn has_session(&mut self, session: &str) -> Result<bool>;
+    // the windows of a session, or of every session when none is given
+    fn list_windows(&mut self, session: Option<&str>) -> Result<Vec<WindowInfo>>;
+    fn list_panes(&mut self, window: &WindowInfo) -> Result<Vec<PaneInfo>>;
+    // name of the session the current client is attached to
+    fn current_session(&mut self) -> Result<String>;
+    // `env` holds `KEY=value` pairs for the first pane of the window,
+    // `size` is the width and height of its windows until a client attaches
+    fn new_session(
+        &mut self,
+        session: &str,
+        window: &str,
+        dir: &str,
+        env: &[String],
+        size: Option<(u32, u32)>,
+    ) -> Result<()>;
+    fn new_window(&mut self, session: &str, window: &str, dir: &str, env: &[String]) -> Result<()>;
+    fn split_window(&mut self, target: &str, dir: &str, env: &[String]) -> Result<()>;
+    fn set_window_option(&mut self, target: &str, option: &str, value: &str) -> Result<()>;
+    fn window_size(&mut self, target: &str) -> Result<(u32, u32)>;
+    fn select_layout(&mut self, target: &str, layout: &str) -> Result<()>;
+    // each key is a tmux key name or a string to type
+    fn send_keys(&mut self, target: &str, keys: &[&str]) -> Result<()>;
+    fn select_pane(&mut self, target: &str, title: Option<&str>) -> Result<()>;
+    fn zoom_pane(&mut self, target: &str) -> Result<()>;
+    fn select_window(&mut self, target: &str) -> Result<()>;
+    fn switch_client(&mut self, target: &str) -> Result<()>;
+    fn attach_session(&mut self, target: &str) -> Result<()>;
+    fn kill_session(&mut self, session: &str) -> Result<()>;
+    fn kill_window(&mut self, target: &str) -> Result<()>;
+    // sends any commands that are being held back
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Runs every operation against a tmux server, the default one unless a socket is given
+#[derive(Debug)]
+pub struct TmuxBackend {
+    // `tmux -L`
+    pub socket_name: Option<String>,
+    // `tmux -S`
+    pub socket_path: Option<PathBuf>,
+    // holds commands back and sends them to tmux in one call when it's
+    // flushed, or before anything is asked of tmux
+    pub batch: bool,
+    // the arguments of every command that's waiting to be sent
+    queue: Vec<Vec<String>>,
+    // how many times tmux has been run
+    calls: usize,
+}
+
+impl Default for TmuxBackend {
+    fn default() -> TmuxBackend {
+        TmuxBackend::new(None, None)
+    }
+}
+
+// Checks that a tmux command exited successfully,
+// failing with tmux's own message when it didn't
+fn check(output: TmuxOutput) -> Result<TmuxOutput> {
+    if !output.success() {
+        let stderr = String::from_utf8_lossy(&output.0.stderr).trim().to_string();
+        return Err(if stderr.is_empty() {
+            anyhow!("tmux exited with {}", output.0.status)
+        } else {
+            anyhow!("{}", stderr)
+        });
+    }
+    Ok(output)
+}
+
+// Whether tmux failed because there's no server running on its socket. tmux says
+// so differently depending on its version and whether a stale socket was left behind
+pub fn is_no_server(err: &anyhow::Error) -> bool {
+    let message = err.to_string();
+    message.starts_with("no server running")
+        || (message.starts_with("error connecting to")
+            && (message.ends_with("(No such file or directory)")
+                || message.ends_with("(Connection refused)")))
+}
+
+fn output_lines(output: TmuxOutput) -> Result<Vec<String>> {
+    Ok(String::from_utf8(check(output)?.0.stdout)?
+        .lines()
+        .map(String::from)
+        .collect())
+}
+
+// tmux escapes tabs in formats, and session names can't contain a `:`
+const SEPARATOR: &str = "|:|";
+const WINDOW_FORMAT: &str = "#{session_name}|:|#{window_index}|:|#{window_active}|:|#{window_panes}|:|#{session_attached}|:|#{@dmux_root}|:|#{@dmux_profile}|:|#{@dmux_created}|:|#{window_layout}|:|#{window_name}";
+const PANE_FORMAT: &str = "#{pane_id}|:|#{pane_index}|:|#{pane_current_command}|:|#{pane_start_command}|:|#{pane_current_path}";
+
+fn parse_window(line: &str) -> Result<WindowInfo> {
+    let fields: Vec<&str> = line.splitn(10, SEPARATOR).collect();
+    match fields[..] {
+        [session, index, active, panes, attached, root, profile, created, layout, name] => {
+            Ok(WindowInfo {
+                session: session.to_string(),
+                index: index.parse()?,
+                active: active == "1",
+                panes: panes.parse()?,
+                // the number of clients attached to the session
+                attached: attached != "0",
+                root: (!root.is_empty()).then(|| PathBuf::from(root)),
+                profile: (!profile.is_empty()).then(|| profile.to_string()),
+                created: created.parse().ok(),
+                layout: layout.to_string(),
+                name: name.to_string(),
+            })
+        }
+        _ => Err(anyhow!("Couldn't read window from {:?}", line)),
+    }
+}
+
+fn parse_pane(line: &str) -> Result<PaneInfo> {
+    let fields: Vec<&str> = line.splitn(5, SEPARATOR).collect();
+    match fields[..] {
+        [id, index, command, start_command, path] => Ok(PaneInfo {
+            id: id.trim_start_matches('%').parse()?,
+            index: index.parse()?,
+            command: command.to_string(),
+            start_command: start_command.to_string(),
+            path: PathBuf::from(path),
+        }),
+        _ => Err(anyhow!("Couldn't read pane from {:?}", line)),
+    }
+}
+
+// tmux takes an argument ending in `;` as the end of the command
+// it's part of, unless the `;` is escaped
+fn escape_separator(arg: &str) -> String {
+    match arg.strip_suffix(';') {
+        Some(rest) => format!("{}\\;", rest),
+        None => arg.to_string(),
+    }
+}
+
+// The arguments of several commands for a single tmux call
+fn batch_args(commands: &[Vec<String>]) -> Vec<String> {
+    commands
+        .iter()
+        .map(|command| command.iter().map(|arg| escape_separator(arg)).collect())
+        .collect::<Vec<Vec<String>>>()
+        .join(&";".to_string())
+}
+
+impl TmuxBackend {
+    pub fn new(socket_name: Option<String>, socket_path: Option<PathBuf>) -> TmuxBackend {
+        TmuxBackend {
+            socket_name,
+            socket_path,
+            batch: true,
+            queue: vec![],
+            calls: 0,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn calls(&self) -> usize {
+        self.calls
+    }
+
+    fn tmux(&self) -> TmuxCommand<'static> {
+        TmuxCommand::new()
+    }
+
+    // `-L`/`-S` for the server this talks to
+    pub fn server_args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if let Some(socket_name) = &self.socket_name {
+            args.push("-L".to_string());
+            args.push(socket_name.clone());
+        }
+        if let Some(socket_path) = &self.socket_path {
+            args.push("-S".to_string());
+            args.push(socket_path.to_string_lossy().into_owned());
+        }
+        args
+    }
+
+    // tmux_interface leaves out the server arguments of the command a
+    // subcommand was made from, so they're added to every subcommand here.
+    // Queued commands go first, since the command might depend on them
+    fn output(&mut self, mut command: TmuxCommand) -> Result<TmuxOutput> {
+        self.send_queued()?;
+        let args = self.server_args();
+        if !args.is_empty() {
+            command.bin_args = Some(args.into_iter().map(Into::into).collect());
+        }
+        if let Some(cmd_args) = command.cmd_args.as_mut() {
+            for arg in cmd_args.iter_mut() {
+                *arg = escape_separator(arg).into();
+            }
+        }
+        self.calls += 1;
+        command.output().context("Couldn't run tmux")
+    }
+
+    pub fn is_installed(&self) -> bool {
+        // tmux failing to start at all means it isn't installed
+        std::process::Command::new("tmux")
+            .args(self.server_args())
+            .arg("-V")
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false)
+    }
+
+    // Where the server listens, which only a running server can tell
+    // for a socket name since tmux resolves the directory it goes in
+    fn server_socket_path(&mut self) -> Result<PathBuf> {
+        if let Some(socket_path) = &self.socket_path {
+            return Ok(socket_path.clone());
+        }
+        let mut display = self.tmux().display_message();
+        display.0.push_flag("-p");
+        display.message("#{socket_path}");
+        output_lines(self.output(display.0)?)?
+            .into_iter()
+            .next()
+            .map(PathBuf::from)
+            .ok_or_else(|| anyhow!("tmux didn't say where its socket is"))
+    }
+
+    fn run(&mut self, command: TmuxCommand) -> Result<()> {
+        if !self.batch {
+            return check(self.output(command)?).map(drop);
+        }
+        let mut args: Vec<String> = command.cmd.into_iter().map(String::from).collect();
+        args.extend(
+            command
+                .cmd_args
+                .unwrap_or_default()
+                .into_iter()
+                .map(String::from),
+        );
+        self.queue.push(args);
+        Ok(())
+    }
+
+    // Sends every queued command to tmux in one call
+    fn send_queued(&mut self) -> Result<()> {
+        if self.queue.is_empty() {
+            return Ok(());
+        }
+        let commands = std::mem::take(&mut self.queue);
+        self.calls += 1;
+        let output = std::process::Command::new("tmux")
+            .args(self.server_args())
+            .args(batch_args(&commands))
+            .output()
+            .context("Couldn't run tmux")?;
+        check(TmuxOutput(output))
+            .map(drop)
+            .context("The batched tmux commands failed, --no-batch runs them one at a time to find which one")
+    }
+}
+
+// $TMUX holds the socket path of the server the client is attached to,
+// followed by the server's pid and the session
+fn same_socket(tmux_var: &str, socket_path: &Path) -> bool {
+    let client_socket = Path::new(tmux_var.split(',').next().unwrap_or_default());
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    canonical(client_socket) == canonical(socket_path)
+}
+
+impl Backend for TmuxBackend {
+    fn in_tmux(&mut self) -> bool {
+        let tmux_var = match std::env::var("TMUX") {
+            Ok(tmux_var) => tmux_var,
+            Err(_) => return false,
+        };
+        self.server_socket_path()
+            .map(|socket_path| same_socket(&tmux_var, &socket_path))
+            .unwrap_or(false)
+    }
+
+    fn has_session(&mut self, session: &str) -> Result<bool> {
+        let mut has_session = self.tmux().has_session();
+        has_session.target_session(session);
+        // has-session exits unsuccessfully when the session doesn't exist
+        Ok(self.output(has_session.0)?.success())
+    }
+
+    fn list_windows(&mut self, session: Option<&str>) -> Result<Vec<WindowInfo>> {
+        let mut list_windows = self.tmux().list_windows();
+        list_windows.format(WINDOW_FORMAT);
+        match session {
+            Some(session) => list_windows.target_session(session),
+            None => list_windows.all(),
+        };
+        output_lines(self.output(list_windows.0)?)?
+            .iter()
+            .map(|line| parse_window(line))
+            .collect()
+    }
+
+    fn list_panes(&mut self, window: &WindowInfo) -> Result<Vec<PaneInfo>> {
+        let mut list_panes = self.tmux().list_panes();
+        list_panes
+            .format(PANE_FORMAT)
+            .target(format!("{}:{}", window.session, window.index));
+        output_lines(self.output(list_panes.0)?)?
+            .iter()
+            .map(|line| parse_pane(line))
+            .collect()
+    }
+
+    fn current_session(&mut self) -> Result<String> {
+        let mut display = self.tmux().display_message();
+        // `-p` is only exposed by tmux_interface for tmux >= 2.9
+        display.0.push_flag("-p");
+        display.message("#{session_name}");
+        output_lines(self.output(display.0)?)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Uh-oh, looks like you're not in a tmux session!"))
+    }
+
+    fn new_session(
+        &mut self,
+        session: &str,
+        window: &str,
+        dir: &str,
+        env: &[String],
+        size: Option<(u32, u32)>,
+    ) -> Result<()> {
+        let mut new_session = self.tmux().new_session();
+        new_session
+            .session_name(session.to_string())
+            .start_directory(dir.to_string())
+            .detached()
+            .window_name(window.to_string());
+        if let Some((width, height)) = size {
+            new_session.width(width as usize).height(height as usize);
+        }
+        // `-e` is only exposed by tmux_interface for tmux >= 3.1
+        for var in env {
+            new_session.0.push_option("-e", var.clone());
+        }
+        self.run(new_session.0)
+    }
+
+    fn new_window(&mut self, session: &str, window: &str, dir: &str, env: &[String]) -> Result<()> {
+        let mut new_window = self.tmux().new_window();
+        new_window
+            .window_name(window.to_string())
+            .target_window(format!("{}:", session))
+            .start_directory(dir.to_string())
+            .detached();
+        for var in env {
+            new_window.0.push_option("-e", var.clone());
+        }
+        self.run(new_window.0)
+    }
+
+    fn split_window(&mut self, target: &str, dir: &str, env: &[String]) -> Result<()> {
+        let mut split_window = self.tmux().split_window();
+        split_window
+            .start_directory(dir.to_string())
+            .target_pane(target.to_string());
+        for var in env {
+            split_window.0.push_option("-e", var.clone());
+        }
+        self.run(split_window.0)
+    }
+
+    fn set_window_option(&mut self, target: &str, option: &str, value: &str) -> Result<()> {
+        let mut set_window_option = self.tmux().set_window_option();
+        set_window_option
+            .target_window(target.to_string())
+            .option(option.to_string())
+            .value(value.to_string());
+        self.run(set_window_option.0)
+    }
+
+    fn window_size(&mut self, target: &str) -> Result<(u32, u32)> {
+        let mut display = self.tmux().display_message();
+        display.0.push_flag("-p");
+        display
+            .target_pane(target.to_string())
+            .message("#{window_width} #{window_height}");
+        let lines = output_lines(self.output(display.0)?)?;
+        let size = lines.first().map(String::as_str).unwrap_or_default();
+        let (width, height) = size
+            .trim()
+            .split_once(' ')
+            .ok_or_else(|| anyhow!("Couldn't read window size from {:?}", size))?;
+        Ok((width.parse()?, height.parse()?))
+    }
+
+    fn select_layout(&mut self, target: &str, layout: &str) -> Result<()> {
+        let mut select_layout = self.tmux().select_layout();
+        select_layout
+            .target_pane(target.to_string())
+            .layout_name(layout.to_string());
+        self.run(select_layout.0)
+    }
+
+    fn send_keys(&mut self, target: &str, keys: &[&str]) -> Result<()> {
+        let mut send_keys = self.tmux().send_keys();
+        send_keys.target_pane(target.to_string());
+        for key in keys {
+            send_keys.key(key.to_string());
+        }
+        self.run(send_keys.0)
+    }
+
+    fn select_pane(&mut self, target: &str, title: Option<&str>) -> Result<()> {
+        let mut select_pane = self.tmux().select_pane();
+        select_pane.target_pane(target.to_string());
+        if let Some(title) = title {
+            select_pane.title(title.to_string());
+        }
+        self.run(select_pane.0)
+    }
+
+    fn zoom_pane(&mut self, target: &str) -> Result<()> {
+        let mut resize_pane = self.tmux().resize_pane();
+        resize_pane.target_pane(target.to_string()).zoom();
+        self.run(resize_pane.0)
+    }
+
+    fn select_window(&mut self, target: &str) -> Result<()> {
+        let mut select_window = self.tmux().select_window();
+        select_window.target_window(target.to_string());
+        self.run(select_window.0)
+    }
+
+    fn switch_client(&mut self, target: &str) -> Result<()> {
+        let mut switch_client = self.tmux().switch_client();
+        switch_client.target_session(target.to_string());
+        // run straight away, tmux would otherwise hold the queue until the client detaches
+        check(self.output(switch_client.0)?).map(drop)
+    }
+
+    fn attach_session(&mut self, target: &str) -> Result<()> {
+        let mut attach_session = self.tmux().attach_session();
+        attach_session.target_session(target.to_string());
+        check(self.output(attach_session.0)?).map(drop)
+    }
+
+    fn kill_session(&mut self, session: &str) -> Result<()> {
+        let mut kill_session = self.tmux().kill_session();
+        kill_session.target_session(session.to_string());
+        self.run(kill_session.0)
+    }
+
+    fn kill_window(&mut self, target: &str) -> Result<()> {
+        let mut kill_window = self.tmux().kill_window();
+        kill_window.target_window(target.to_string());
+        self.run(kill_window.0)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.send_queued()
+    }
+}
+
+// Quotes an argument for a POSIX shell, leaving it bare when that's already safe
+pub fn shell_quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_-./:=@%+,".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+// Records the tmux commands it's given instead of running them, answering
+// queries from a tmux server when it has one, otherwise from the sessions
+// and windows it has been told about
+#[derive(Debug, Default)]
+pub struct Recorder {
+    // session name and the names of its windows
+    pub sessions: Vec<(String, Vec<String>)>,
+    // the arguments of every command after `tmux`
+    pub commands: Vec<Vec<String>>,
+    pub in_tmux: bool,
+    // `-L`/`-S` for the server the commands are meant for
+    pub server_args: Vec<String>,
+    // asked whatever tmux is asked, since that doesn't change anything
+    pub server: Option<TmuxBackend>,
+}
+
+impl Recorder {
+    // Asks the server what exists and records the commands that would change
+    // it, so they're the ones setting up the workspace would run right now
+    pub fn dry_run(tmux: &TmuxBackend) -> Recorder {
+        Recorder {
+            server_args: tmux.server_args(),
+            server: Some(TmuxBackend::new(
+                tmux.socket_name.clone(),
+                tmux.socket_path.clone(),
+            )),
+            ..Recorder::default()
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_session(session: &str, windows: &[&str]) -> Recorder {
+        Recorder {
+            sessions: vec![(
+                session.to_string(),
+                windows.iter().map(|window| window.to_string()).collect(),
+            )],
+            ..Recorder::default()
+        }
+    }
+
+    fn record(&mut self, args: &[&str], env: &[String]) -> Result<()> {
+        let mut command: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        for var in env {
+            command.push("-e".to_string());
+            command.push(var.clone());
+        }
+        self.commands.push(command);
+        Ok(())
+    }
+
+    // every command as a shell-quoted tmux invocation
+    pub fn script(&self) -> Vec<String> {
+        self.commands
+            .iter()
+            .map(|command| {
+                let mut line = vec!["tmux".to_string()];
+                line.extend(self.server_args.iter().map(|arg| shell_quote(arg)));
+                line.extend(command.iter().map(|arg| shell_quote(arg)));
+                line.join(" ")
+            })
+            .collect()
+    }
+
+    // every command without `tmux` in front, to compare against in tests
+    #[cfg(test)]
+    pub fn command_lines(&self) -> Vec<String> {
+        self.commands
+            .iter()
+            .map(|command| {
+                command
+                    .iter()
+                    .map(|arg| shell_quote(arg))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+
+    // the names of the commands that were run, without their arguments
+    #[cfg(test)]
+    pub fn command_names(&self) -> Vec<&str> {
+        self.commands
+            .iter()
+            .map(|command| command[0].as_str())
+            .collect()
+    }
+}
+
+impl Backend for Recorder {
+    fn in_tmux(&mut self) -> bool {
+        match &mut self.server {
+            Some(server) => server.in_tmux(),
+            None => self.in_tmux,
+        }
+    }
+
+    fn has_session(&mut self, session: &str) -> Result<bool> {
+        match &mut self.server {
+            Some(server) => server.has_session(session),
+            None => Ok(self.sessions.iter().any(|(name, _)| name == session)),
+        }
+    }
+
+    fn list_windows(&mut self, session: Option<&str>) -> Result<Vec<WindowInfo>> {
+        if let Some(server) = &mut self.server {
+            return server.list_windows(session);
+        }
+        Ok(self
+            .sessions
+            .iter()
+            .filter(|(name, _)| session.is_none_or(|session| session == name))
+            .flat_map(|(name, windows)| {
+                windows
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, window)| WindowInfo {
+                        session: name.clone(),
+                        index: index as u32,
+                        name: window.clone(),
+                        active: index == 0,
+                        layout: String::new(),
+                        panes: 1,
+                        attached: false,
+                        root: None,
+                        profile: None,
+                        created: None,
+                    })
+            })
+            .collect())
+    }
+
+    fn list_panes(&mut self, window: &WindowInfo) -> Result<Vec<PaneInfo>> {
+        match &mut self.server {
+            Some(server) => server.list_panes(window),
+            None => Ok(vec![]),
+        }
+    }
+
+    fn current_session(&mut self) -> Result<String> {
+        if let Some(server) = &mut self.server {
+            return server.current_session();
+        }
+        self.sessions
+            .first()
+            .map(|(name, _)| name.clone())
+            .ok_or_else(|| anyhow!("There are no sessions"))
+    }
+
+    fn new_session(
+        &mut self,
+        session: &str,
+        window: &str,
+        dir: &str,
+        env: &[String],
+        size: Option<(u32, u32)>,
+    ) -> Result<()> {
+        self.sessions
+            .push((session.to_string(), vec![window.to_string()]));
+        let mut args = vec!["new-session", "-d", "-s", session, "-n", window, "-c", dir];
+        let size = size.map(|(width, height)| (width.to_string(), height.to_string()));
+        if let Some((width, height)) = &size {
+            args.extend(["-x", width, "-y", height]);
+        }
+        self.record(&args, env)
+    }
+
+    fn new_window(&mut self, session: &str, window: &str, dir: &str, env: &[String]) -> Result<()> {
+        if let Some((_, windows)) = self.sessions.iter_mut().find(|(name, _)| name == session) {
+            windows.push(window.to_string());
+        }
+        let target = format!("{}:", session);
+        self.record(
+            &["new-window", "-d", "-t", &target, "-n", window, "-c", dir],
+            env,
+        )
+    }
+
+    fn split_window(&mut self, target: &str, dir: &str, env: &[String]) -> Result<()> {
+        self.record(&["split-window", "-t", target, "-c", dir], env)
+    }
+
+    fn set_window_option(&mut self, target: &str, option: &str, value: &str) -> Result<()> {
+        self.record(&["set-window-option", "-t", target, option, value], &[])
+    }
+
+    // without a server, the size tmux gives new detached sessions
+    // unless it's configured otherwise
+    fn window_size(&mut self, target: &str) -> Result<(u32, u32)> {
+        match &mut self.server {
+            Some(server) => server.window_size(target),
+            None => Ok((80, 24)),
+        }
+    }
+
+    fn select_layout(&mut self, target: &str, layout: &str) -> Result<()> {
+        self.record(&["select-layout", "-t", target, layout], &[])
+    }
+
+    fn send_keys(&mut self, target: &str, keys: &[&str]) -> Result<()> {
+        let mut args = vec!["send-keys", "-t", target];
+        args.extend(keys);
+        self.record(&args, &[])
+    }
+
+    fn select_pane(&mut self, target: &str, title: Option<&str>) -> Result<()> {
+        match title {
+            Some(title) => self.record(&["select-pane", "-t", target, "-T", title], &[]),
+            None => self.record(&["select-pane", "-t", target], &[]),
+        }
+    }
+
+    fn zoom_pane(&mut self, target: &str) -> Result<()> {
+        self.record(&["resize-pane", "-t", target, "-Z"], &[])
+    }
+
+    fn select_window(&mut self, target: &str) -> Result<()> {
+        self.record(&["select-window", "-t", target], &[])
+    }
+
+    fn switch_client(&mut self, target: &str) -> Result<()> {
+        self.record(&["switch-client", "-t", target], &[])
+    }
+
+    fn attach_session(&mut self, target: &str) -> Result<()> {
+        self.record(&["attach-session", "-t", target], &[])
+    }
+
+    fn kill_session(&mut self, session: &str) -> Result<()> {
+        self.sessions.retain(|(name, _)| name != session);
+        self.record(&["kill-session", "-t", session], &[])
+    }
+
+    fn kill_window(&mut self, target: &str) -> Result<()> {
+        if let Some((session, window)) = target.split_once(':') {
+            for (_, windows) in self.sessions.iter_mut().filter(|(name, _)| name == session) {
+                windows.retain(|name| name != window);
+            }
+        }
+        self.record(&["kill-window", "-t", target], &[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_window_reads_list_windows_format() {
+        let window = parse_window(
+            "dev|:|2|:|1|:|2|:|1|:|/home/me/dev/app|:|rust|:|1792315646|:|34ed,230x56,0,0{132x56,0,0,3,97x56,133,0,222}|:|my window",
+        )
+        .unwrap();
+        assert_eq!(
+            window,
+            WindowInfo {
+                session: "dev".to_owned(),
+                index: 2,
+                name: "my window".to_owned(),
+                active: true,
+                layout: "34ed,230x56,0,0{132x56,0,0,3,97x56,133,0,222}".to_owned(),
+                panes: 2,
+                attached: true,
+                root: Some(PathBuf::from("/home/me/dev/app")),
+                profile: Some("rust".to_owned()),
+                created: Some(1792315646),
+            }
+        );
+        // windows dmux didn't open have no root
+        let window = parse_window("dev|:|0|:|0|:|1|:|0|:||:||:||:|c4be,80x24,0,0,0|:|zsh").unwrap();
+        assert_eq!((window.root, window.created), (None, None));
+        assert!(!window.attached);
+        assert!(parse_window("dev|:|2").is_err());
+    }
+
+    #[test]
+    fn parse_pane_reads_list_panes_format() {
+        let pane = parse_pane("%17|:|0|:|nvim|:||:|/home/me/dev/dmux").unwrap();
+        assert_eq!(pane.id, 17);
+        assert_eq!(pane.index, 0);
+        assert_eq!(pane.command, "nvim");
+        assert_eq!(pane.path, PathBuf::from("/home/me/dev/dmux"));
+    }
+
+    fn output(code: i32, stderr: &str) -> TmuxOutput {
+        use std::os::unix::process::ExitStatusExt;
+        TmuxOutput(std::process::Output {
+            // wait statuses keep the exit code in the second byte
+            status: std::process::ExitStatus::from_raw(code << 8),
+            stdout: vec![],
+            stderr: stderr.as_bytes().to_vec(),
+        })
+    }
+
+    #[test]
+    fn check_fails_when_tmux_exits_unsuccessfully() {
+        assert!(check(output(0, "")).is_ok());
+        let err = check(output(1, "can't find session: dev\n")).unwrap_err();
+        assert_eq!(err.to_string(), "can't find session: dev");
+        let err = check(output(1, "")).unwrap_err();
+        assert!(err.to_string().starts_with("tmux exited with"), "{}", err);
+    }
+
+    #[test]
+    fn is_no_server_only_matches_a_missing_server() {
+        for stderr in [
+            "no server running on /tmp/tmux-1000/default",
+            "error connecting to /tmp/tmux-1000/pair (No such file or directory)",
+            "error connecting to /tmp/tmux-1000/pair (Connection refused)",
+        ] {
+            assert!(is_no_server(&check(output(1, stderr)).unwrap_err()));
+        }
+        assert!(!is_no_server(
+            &check(output(
+                1,
+                "error connecting to /tmp/pair (Permission denied)"
+            ))
+            .unwrap_err()
+        ));
+        assert!(!is_no_server(
+            &check(output(1, "unknown option -- a")).unwrap_err()
+        ));
+    }
+
+    #[test]
+    fn same_socket_compares_the_socket_in_tmux_var() {
+        let socket = Path::new("/tmp/tmux-1000/pair");
+        assert!(same_socket("/tmp/tmux-1000/pair,4242,3", socket));
+        assert!(!same_socket("/tmp/tmux-1000/default,4242,3", socket));
+    }
+
+    #[test]
+    fn server_args_select_the_socket() {
+        let backend = TmuxBackend::new(Some("pair".to_owned()), None);
+        assert_eq!(backend.server_args(), vec!["-L", "pair"]);
+        let backend = TmuxBackend::new(None, Some(PathBuf::from("/tmp/pair.sock")));
+        assert_eq!(backend.server_args(), vec!["-S", "/tmp/pair.sock"]);
+        assert!(TmuxBackend::default().server_args().is_empty());
+    }
+
+    #[test]
+    fn batch_args_separate_commands_and_escape_their_semicolons() {
+        let commands = vec![
+            vec![
+                "split-window".to_owned(),
+                "-t".to_owned(),
+                "dev:app".to_owned(),
+            ],
+            vec![
+                "send-keys".to_owned(),
+                "-t".to_owned(),
+                "dev:app.1".to_owned(),
+                "make;".to_owned(),
+                "Enter".to_owned(),
+            ],
+        ];
+        assert_eq!(
+            batch_args(&commands),
+            vec![
+                "split-window",
+                "-t",
+                "dev:app",
+                ";",
+                "send-keys",
+                "-t",
+                "dev:app.1",
+                "make\\;",
+                "Enter"
+            ]
+        );
+    }
+
+    #[test]
+    fn batched_commands_wait_for_a_flush() {
+        let mut backend = TmuxBackend::new(Some("dmux-never-started".to_owned()), None);
+        backend.select_window("dev:app").unwrap();
+        backend.zoom_pane("dev:app.0").unwrap();
+        assert_eq!(
+            backend.queue,
+            vec![
+                vec!["selectw", "-t", "dev:app"],
+                vec!["resizep", "-t", "dev:app.0", "-Z"],
+            ]
+        );
+    }
+
+    #[test]
+    fn shell_quote_only_quotes_when_needed() {
+        assert_eq!(shell_quote("dev:app.0"), "dev:app.0");
+        assert_eq!(shell_quote("cargo watch"), "'cargo watch'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn recorder_script_quotes_every_argument() {
+        let mut recorder = Recorder {
+            server_args: vec!["-L".to_owned(), "pair".to_owned()],
+            ..Recorder::default()
+        };
+        recorder
+            .send_keys("dev:app.1", &["yarn watch", "Enter"])
+            .unwrap();
+        assert_eq!(
+            recorder.script(),
+            vec!["tmux -L pair send-keys -t dev:app.1 'yarn watch' Enter"]
+        );
+    }
+
+    #[test]
+    fn recorder_tracks_the_windows_it_creates() {
+        let mut recorder = Recorder::default();
+        assert!(!recorder.has_session("dev").unwrap());
+        recorder
+            .new_session("dev", "app", "/app", &[], None)
+            .unwrap();
+        recorder.new_window("dev", "logs", "/app", &[]).unwrap();
+        let windows = recorder.list_windows(Some("dev")).unwrap();
+        assert_eq!(windows[1].name, "logs");
+        assert_eq!(recorder.command_names(), vec!["new-session", "new-window"]);
+    }
+}
@@ -0,0 +1,258 @@
+// Finding and editing the user's dmux config file
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl ConfigFormat {
+    fn from_path(path: &Path) -> Result<ConfigFormat> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Ok(ConfigFormat::Toml),
+            Some("json") => Ok(ConfigFormat::Json),
+            Some("yaml") | Some("yml") => Ok(ConfigFormat::Yaml),
+            _ => Err(anyhow!(
+                "dmux can only add profiles to TOML, JSON or YAML config files, not {:?}",
+                path
+            )),
+        }
+    }
+}
+
+impl ConfigFormat {
+    pub fn file_format(self) -> config::FileFormat {
+        match self {
+            ConfigFormat::Toml => config::FileFormat::Toml,
+            ConfigFormat::Json => config::FileFormat::Json,
+            ConfigFormat::Yaml => config::FileFormat::Yaml,
+        }
+    }
+
+    pub fn parse(self, contents: &str) -> Result<serde_json::Value> {
+        Ok(match self {
+            ConfigFormat::Toml => toml::from_str(contents)?,
+            ConfigFormat::Json => serde_json::from_str(contents)?,
+            ConfigFormat::Yaml => serde_yaml::from_str(contents)?,
+        })
+    }
+}
+
+// Paths starting with `~` are in the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), dirs::home_dir()) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            home.join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+const EXTENSIONS: [&str; 6] = ["toml", "json", "yaml", "yml", "hjson", "ini"];
+const LOCAL_EXTENSIONS: [&str; 4] = ["toml", "json", "yaml", "yml"];
+
+// A dmux.local.(toml | json | yaml) file at the root of a directory, which is
+// merged over the profile when it's trusted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalConfig {
+    pub path: PathBuf,
+    pub format: ConfigFormat,
+    pub contents: String,
+}
+
+pub fn find_local_config(dir: &Path) -> Result<Option<LocalConfig>> {
+    for extension in LOCAL_EXTENSIONS {
+        let path = dir.join(format!("dmux.local.{}", extension));
+        if path.is_file() {
+            let contents =
+                fs::read_to_string(&path).with_context(|| format!("Couldn't read {:?}", path))?;
+            return Ok(Some(LocalConfig {
+                format: ConfigFormat::from_path(&path)?,
+                path,
+                contents,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+// Config file paths without their extension, in the order they're merged
+pub fn config_file_bases() -> Result<Vec<PathBuf>> {
+    let config_dir = dirs::config_dir().ok_or_else(|| anyhow!("Config dir couldn't be read"))?;
+    let home_dir = dirs::home_dir().ok_or_else(|| anyhow!("Home directory couldn't be found"))?;
+    Ok(vec![
+        // {xdg_config|~/Library/Application Support}/dmux/dmux.conf.(yaml | json | toml)
+        config_dir.join("dmux/dmux.conf"),
+        // ~/.dmux.conf.(yaml | json | toml)
+        home_dir.join(".dmux.conf"),
+        // ~/.config/dmux/dmux.conf.(yaml | json | toml)
+        home_dir.join(".config/dmux/dmux.conf"),
+    ])
+}
+
+fn with_extension(base: &Path, extension: &str) -> PathBuf {
+    let mut path = base.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+// The first config file that exists, if any do
+pub fn find_config_file() -> Result<Option<PathBuf>> {
+    for base in config_file_bases()? {
+        for extension in EXTENSIONS {
+            let path = with_extension(&base, extension);
+            if path.is_file() {
+                return Ok(Some(path));
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn has_profile(contents: &str, format: ConfigFormat, name: &str) -> Result<bool> {
+    Ok(match format {
+        ConfigFormat::Toml => contents.parse::<toml::Value>()?.get(name).is_some(),
+        ConfigFormat::Json => serde_json::from_str::<serde_json::Value>(contents)?
+            .get(name)
+            .is_some(),
+        ConfigFormat::Yaml => serde_yaml::from_str::<serde_yaml::Value>(contents)?
+            .get(name)
+            .is_some(),
+    })
+}
+
+// Returns the config file with a new profile added to it.
+// TOML and YAML profiles are appended so the rest of the file is left untouched.
+fn with_profile<T: Serialize>(
+    contents: &str,
+    format: ConfigFormat,
+    name: &str,
+    profile: &T,
+) -> Result<String> {
+    if !contents.trim().is_empty() && has_profile(contents, format, name)? {
+        return Err(anyhow!("A profile named {:?} already exists", name));
+    }
+    let mut section = BTreeMap::new();
+    section.insert(name, profile);
+
+    let mut contents = contents.to_string();
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    match format {
+        ConfigFormat::Toml => {
+            if !contents.is_empty() {
+                contents.push('\n');
+            }
+            contents.push_str(&toml::to_string(&section)?);
+        }
+        ConfigFormat::Yaml => {
+            let yaml = serde_yaml::to_string(&section)?;
+            contents.push_str(yaml.trim_start_matches("---\n"));
+        }
+        ConfigFormat::Json => {
+            let mut config = if contents.trim().is_empty() {
+                serde_json::Value::Object(Default::default())
+            } else {
+                serde_json::from_str(&contents)?
+            };
+            config
+                .as_object_mut()
+                .ok_or_else(|| anyhow!("The config file isn't a JSON object"))?
+                .insert(name.to_string(), serde_json::to_value(profile)?);
+            contents = serde_json::to_string_pretty(&config)?;
+            contents.push('\n');
+        }
+    }
+    Ok(contents)
+}
+
+// Adds a profile to the user's config file, creating a TOML one if there isn't one yet
+pub fn add_profile<T: Serialize>(name: &str, profile: &T) -> Result<PathBuf> {
+    let path = match find_config_file()? {
+        Some(path) => path,
+        None => with_extension(&config_file_bases()?[0], "toml"),
+    };
+    let format = ConfigFormat::from_path(&path)?;
+    let contents = if path.exists() {
+        fs::read_to_string(&path).with_context(|| format!("Couldn't read {:?}", path))?
+    } else {
+        String::new()
+    };
+    let contents = with_profile(&contents, format, name, profile)
+        .with_context(|| format!("Couldn't add profile {:?} to {:?}", name, path))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, contents).with_context(|| format!("Couldn't write {:?}", path))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Profile {
+        layout: String,
+        commands: Vec<String>,
+    }
+
+    fn profile() -> Profile {
+        Profile {
+            layout: "tiled".to_owned(),
+            commands: vec!["nvim".to_owned(), "".to_owned()],
+        }
+    }
+
+    #[test]
+    fn with_profile_appends_toml_tables() {
+        let contents = "# my config\nsession_name = \"dev\"\n";
+        let updated = with_profile(contents, ConfigFormat::Toml, "notes", &profile()).unwrap();
+        assert!(updated.starts_with(contents));
+        let config: toml::Value = updated.parse().unwrap();
+        assert_eq!(config["notes"]["layout"].as_str(), Some("tiled"));
+        assert_eq!(config["session_name"].as_str(), Some("dev"));
+    }
+
+    #[test]
+    fn with_profile_appends_yaml_mappings() {
+        let contents = "session_name: dev";
+        let updated = with_profile(contents, ConfigFormat::Yaml, "notes", &profile()).unwrap();
+        let config: serde_yaml::Value = serde_yaml::from_str(&updated).unwrap();
+        assert_eq!(config["notes"]["commands"][0].as_str(), Some("nvim"));
+        assert_eq!(config["session_name"].as_str(), Some("dev"));
+    }
+
+    #[test]
+    fn with_profile_inserts_json_keys() {
+        let contents = "{\"session_name\": \"dev\"}";
+        let updated = with_profile(contents, ConfigFormat::Json, "notes", &profile()).unwrap();
+        let config: serde_json::Value = serde_json::from_str(&updated).unwrap();
+        assert_eq!(config["notes"]["layout"].as_str(), Some("tiled"));
+        assert_eq!(config["session_name"].as_str(), Some("dev"));
+    }
+
+    #[test]
+    fn with_profile_writes_to_empty_files() {
+        for format in [ConfigFormat::Toml, ConfigFormat::Json, ConfigFormat::Yaml] {
+            let updated = with_profile("", format, "notes", &profile()).unwrap();
+            assert!(updated.contains("tiled"), "{}", updated);
+        }
+    }
+
+    #[test]
+    fn with_profile_refuses_to_overwrite_profiles() {
+        let contents = "[notes]\nlayout = \"tiled\"\n";
+        assert!(with_profile(contents, ConfigFormat::Toml, "notes", &profile()).is_err());
+    }
+}
@@ -0,0 +1,556 @@
+// Describes the layout of running tmux windows for `dmux layout`
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::app::{FreezeArgs, LayoutArgs};
+use crate::backend::{shell_quote, Backend};
+use crate::config_file;
+use crate::layout::{Cell, CellKind, Direction, Layout};
+use crate::tmux::{PaneInfo, WindowInfo};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Raw,
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> Result<Self> {
+        match format {
+            "raw" => Ok(OutputFormat::Raw),
+            "json" => Ok(OutputFormat::Json),
+            "toml" => Ok(OutputFormat::Toml),
+            "yaml" => Ok(OutputFormat::Yaml),
+            _ => Err(anyhow!("unknown output format {:?}", format)),
+        }
+    }
+}
+
+// Serializes into one of the structured formats
+pub fn render<T: Serialize>(value: &T, format: OutputFormat) -> Result<String> {
+    match format {
+        OutputFormat::Json => Ok(serde_json::to_string_pretty(value)?),
+        OutputFormat::Toml => Ok(toml::to_string(value)?),
+        OutputFormat::Yaml => Ok(serde_yaml::to_string(value)?),
+        OutputFormat::Raw => Err(anyhow!("raw output isn't a structured format")),
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct Description {
+    // not `windows`, which pasted into the config would be a profile's windows
+    #[serde(rename = "tmux_windows")]
+    windows: Vec<WindowDescription>,
+    // a profile for every window, keyed by its name like in the config file
+    #[serde(flatten)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Serialize, Debug)]
+struct WindowDescription {
+    session: String,
+    name: String,
+    index: u32,
+    active: bool,
+    layout: String,
+    tree: CellDescription,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct CellDescription {
+    width: u32,
+    height: u32,
+    x: u32,
+    y: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    split: Option<Direction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    panes: Vec<CellDescription>,
+}
+
+// The part of a window that can be pasted into the config file as a profile
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub layout: String,
+    pub number_of_panes: u8,
+    pub commands: Vec<String>,
+}
+
+// Shells are what a pane runs when it isn't running anything else
+pub fn is_shell(command: &str) -> bool {
+    let command = command.trim_start_matches('-');
+    let user_shell = std::env::var("SHELL").unwrap_or_default();
+    let user_shell = Path::new(&user_shell)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    command == user_shell
+        || [
+            "sh", "bash", "zsh", "fish", "dash", "ksh", "tcsh", "csh", "nu", "elvish", "xonsh",
+        ]
+        .contains(&command)
+}
+
+// The directory the window was opened in, taken to be
+// the deepest directory that every pane is inside of
+pub fn window_root(panes: &[PaneInfo]) -> Option<PathBuf> {
+    // tmux reports no path for some panes, like dead ones
+    let mut panes = panes.iter().filter(|pane| pane.path.is_absolute());
+    let mut root = panes.next()?.path.clone();
+    for pane in panes {
+        while !pane.path.starts_with(&root) {
+            root.pop();
+        }
+    }
+    // a profile rooted at `/` wouldn't be any less tied to this machine
+    Some(root).filter(|root| root.parent().is_some())
+}
+
+// What the pane was started with or is running, empty for a plain shell
+pub fn running_command(pane: &PaneInfo) -> String {
+    let start_command = pane.start_command.trim_matches('"');
+    if !start_command.is_empty() {
+        start_command.to_string()
+    } else if is_shell(&pane.command) {
+        String::new()
+    } else {
+        pane.command.clone()
+    }
+}
+
+// Where the root appears in a command as a whole path or the start of one,
+// leaving out paths that only start with the same text, like `/srv/app2`
+pub fn root_positions(command: &str, root: &str) -> Vec<usize> {
+    let is_path_char = |c: char| !c.is_whitespace() && !"'\"`;:=&|()<>".contains(c);
+    command
+        .match_indices(root)
+        .map(|(start, _)| start)
+        .filter(|&start| {
+            let after = &command[start + root.len()..];
+            !command[..start].ends_with(is_path_char)
+                && (after.starts_with('/') || !after.starts_with(is_path_char))
+        })
+        .collect()
+}
+
+// Points paths inside `dir` at `relative`, which is
+// the way to `dir` from the directory the command runs in
+fn relative_to(command: &str, dir: &str, relative: &str) -> String {
+    let mut replaced = String::new();
+    let mut end = 0;
+    for start in root_positions(command, dir) {
+        replaced.push_str(&command[end..start]);
+        end = start + dir.len();
+        if relative == "." && command[end..].starts_with('/') {
+            end += 1;
+        } else {
+            replaced.push_str(relative);
+        }
+    }
+    replaced.push_str(&command[end..]);
+    replaced
+}
+
+// What to run in a pane to get it back to the same state,
+// with paths inside the root made relative so the profile works in any directory
+fn pane_command(pane: &PaneInfo, root: Option<&Path>) -> String {
+    let command = running_command(pane);
+    let root = match root {
+        Some(root) => root,
+        None => return command,
+    };
+    let dir = match pane.path.strip_prefix(root) {
+        Ok(dir) => dir,
+        Err(_) => return relative_to(&command, &root.display().to_string(), "."),
+    };
+    // the command runs after the `cd`, so paths are made relative to the pane's directory
+    let up = vec![".."; dir.components().count()].join("/");
+    let command = relative_to(&command, &pane.path.display().to_string(), ".");
+    let command = relative_to(
+        &command,
+        &root.display().to_string(),
+        if up.is_empty() { "." } else { &up },
+    );
+    if dir.as_os_str().is_empty() {
+        command
+    } else if command.is_empty() {
+        format!("cd {}", shell_quote(&dir.display().to_string()))
+    } else {
+        format!(
+            "cd {} && {}",
+            shell_quote(&dir.display().to_string()),
+            command
+        )
+    }
+}
+
+pub fn profile_for(window: &WindowInfo, panes: &[PaneInfo]) -> Profile {
+    let mut panes = panes.to_vec();
+    panes.sort_by_key(|pane| pane.index);
+    let root = window_root(&panes);
+    Profile {
+        layout: window.layout.clone(),
+        number_of_panes: panes.len() as u8,
+        commands: panes
+            .iter()
+            .map(|pane| pane_command(pane, root.as_deref()))
+            .collect(),
+    }
+}
+
+fn describe_cell(cell: &Cell, panes: &[PaneInfo]) -> CellDescription {
+    let (split, pane, children) = match &cell.kind {
+        CellKind::Pane(id) => (
+            None,
+            id.and_then(|id| panes.iter().find(|pane| pane.id == id)),
+            vec![],
+        ),
+        CellKind::Split(direction, children) => (
+            Some(*direction),
+            None,
+            children
+                .iter()
+                .map(|child| describe_cell(child, panes))
+                .collect(),
+        ),
+    };
+    CellDescription {
+        width: cell.width,
+        height: cell.height,
+        x: cell.x,
+        y: cell.y,
+        split,
+        index: pane.map(|pane| pane.index),
+        command: pane.map(|pane| pane.command.clone()),
+        path: pane.map(|pane| pane.path.clone()),
+        panes: children,
+    }
+}
+
+fn describe_window(window: WindowInfo, panes: &[PaneInfo]) -> Result<WindowDescription> {
+    let layout: Layout = window
+        .layout
+        .parse()
+        .with_context(|| format!("tmux gave an unexpected layout for {}", window.name))?;
+    Ok(WindowDescription {
+        tree: describe_cell(&layout.root, panes),
+        session: window.session,
+        name: window.name,
+        index: window.index,
+        active: window.active,
+        layout: window.layout,
+    })
+}
+
+// Names the window's profile after it, numbering windows that share a name
+fn profile_name(window: &WindowInfo, profiles: &BTreeMap<String, Profile>) -> String {
+    // a top level `windows` would be the windows of the default profile
+    let taken =
+        |name: &str| ["windows", "tmux_windows"].contains(&name) || profiles.contains_key(name);
+    let mut name = window.name.clone();
+    let mut number = 1;
+    while taken(&name) {
+        number += 1;
+        name = format!("{}-{}", window.name, number);
+    }
+    name
+}
+
+fn describe(windows: Vec<WindowInfo>, tmux: &mut dyn Backend) -> Result<Description> {
+    let mut description = Description {
+        windows: vec![],
+        profiles: BTreeMap::new(),
+    };
+    for window in windows {
+        let panes = tmux.list_panes(&window)?;
+        let name = profile_name(&window, &description.profiles);
+        description
+            .profiles
+            .insert(name, profile_for(&window, &panes));
+        description.windows.push(describe_window(window, &panes)?);
+    }
+    Ok(description)
+}
+
+// The active window of the session, or every window with `all`,
+// or the window with the given name or index
+fn selected_windows(
+    tmux: &mut dyn Backend,
+    session: Option<&str>,
+    window: Option<&str>,
+    all: bool,
+) -> Result<Vec<WindowInfo>> {
+    let session = match (session, all) {
+        (Some(session), _) => Some(session.to_string()),
+        (None, true) => None,
+        (None, false) => Some(tmux.current_session()?),
+    };
+    let windows: Vec<WindowInfo> = tmux
+        .list_windows(session.as_deref())?
+        .into_iter()
+        .filter(|info| match window {
+            Some(name) => info.name == name || info.index.to_string() == name,
+            None => all || info.active,
+        })
+        .collect();
+    if windows.is_empty() {
+        return Err(match (window, session) {
+            (Some(name), _) => anyhow!("No window named {:?} found", name),
+            (None, Some(session)) => anyhow!("Session {:?} has no active window", session),
+            (None, None) => anyhow!("There are no windows to describe"),
+        });
+    }
+    Ok(windows)
+}
+
+pub fn print_layouts(args: LayoutArgs, tmux: &mut dyn Backend) -> Result<()> {
+    let windows = selected_windows(
+        tmux,
+        args.session.as_deref(),
+        args.window.as_deref(),
+        args.all,
+    )?;
+    if args.format == OutputFormat::Raw {
+        if windows.len() == 1 {
+            println!("{}", windows[0].layout);
+        } else {
+            for window in windows {
+                println!("{}:{} {}", window.session, window.index, window.layout);
+            }
+        }
+        return Ok(());
+    }
+    let description = describe(windows, tmux)?;
+    print!("{}", render(&description, args.format)?);
+    Ok(())
+}
+
+pub fn freeze(args: FreezeArgs, tmux: &mut dyn Backend) -> Result<()> {
+    let window =
+        selected_windows(tmux, args.session.as_deref(), args.window.as_deref(), false)?.remove(0);
+    let panes = tmux.list_panes(&window)?;
+    let name = args.name.unwrap_or_else(|| window.name.clone());
+    let path = config_file::add_profile(&name, &profile_for(&window, &panes))?;
+    println!(
+        "Added profile {:?} to {}, open it with `dmux -P {} <dir>`",
+        name,
+        path.display(),
+        name
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Recorder;
+    use crate::config_file::ConfigFormat;
+
+    const LAYOUT: &str = "34ed,230x56,0,0{132x56,0,0,3,97x56,133,0,222}";
+
+    fn window() -> WindowInfo {
+        WindowInfo {
+            session: "dev".to_owned(),
+            index: 1,
+            name: "dmux".to_owned(),
+            active: true,
+            layout: LAYOUT.to_owned(),
+            panes: 2,
+            attached: true,
+            root: None,
+            profile: None,
+            created: None,
+        }
+    }
+
+    fn panes() -> Vec<PaneInfo> {
+        vec![
+            PaneInfo {
+                id: 222,
+                index: 1,
+                command: "bash".to_owned(),
+                start_command: "".to_owned(),
+                path: PathBuf::from("/home/me/dev/dmux"),
+            },
+            PaneInfo {
+                id: 3,
+                index: 0,
+                command: "nvim".to_owned(),
+                start_command: "".to_owned(),
+                path: PathBuf::from("/home/me/dev/dmux/src"),
+            },
+        ]
+    }
+
+    #[test]
+    fn profile_for_orders_commands_by_pane_and_skips_shells() {
+        assert_eq!(
+            profile_for(&window(), &panes()),
+            Profile {
+                layout: LAYOUT.to_owned(),
+                number_of_panes: 2,
+                commands: vec!["cd src && nvim".to_owned(), "".to_owned()],
+            }
+        );
+    }
+
+    fn pane(command: &str, start_command: &str, path: &str) -> PaneInfo {
+        PaneInfo {
+            id: 0,
+            index: 0,
+            command: command.to_owned(),
+            start_command: start_command.to_owned(),
+            path: PathBuf::from(path),
+        }
+    }
+
+    #[test]
+    fn window_root_is_the_deepest_shared_dir() {
+        let panes = vec![
+            pane("nvim", "", "/home/me/dev/dmux/src"),
+            pane("bash", "", "/home/me/dev/dmux/tests"),
+        ];
+        assert_eq!(
+            window_root(&panes),
+            Some(PathBuf::from("/home/me/dev/dmux"))
+        );
+        let panes = vec![pane("nvim", "", "/home"), pane("bash", "", "/tmp")];
+        assert_eq!(window_root(&panes), None);
+        // panes without a path are left out
+        let panes = vec![
+            pane("nvim", "", "/home/me/dev/dmux/src"),
+            pane("bash", "", ""),
+        ];
+        assert_eq!(
+            window_root(&panes),
+            Some(PathBuf::from("/home/me/dev/dmux/src"))
+        );
+        assert_eq!(window_root(&[pane("bash", "", "")]), None);
+    }
+
+    #[test]
+    fn pane_command_makes_paths_relative_to_the_root() {
+        let root = Path::new("/home/me/dev/dmux");
+        assert_eq!(
+            pane_command(&pane("nvim", "", "/home/me/dev/dmux"), Some(root)),
+            "nvim"
+        );
+        assert_eq!(
+            pane_command(&pane("bash", "", "/home/me/dev/dmux/src"), Some(root)),
+            "cd src"
+        );
+        assert_eq!(
+            pane_command(
+                &pane(
+                    "tail",
+                    "\"tail -f /home/me/dev/dmux/log/dev.log\"",
+                    "/home/me/dev/dmux/log"
+                ),
+                Some(root)
+            ),
+            "cd log && tail -f dev.log"
+        );
+        assert_eq!(
+            pane_command(
+                &pane(
+                    "cat",
+                    "\"cat /home/me/dev/dmux/src/main.rs /home/me/dev/dmux\"",
+                    "/home/me/dev/dmux/log/old"
+                ),
+                Some(root)
+            ),
+            "cd log/old && cat ../../src/main.rs ../.."
+        );
+        assert_eq!(
+            pane_command(&pane("bash", "", "/home/me/dev/dmux/my notes"), Some(root)),
+            "cd 'my notes'"
+        );
+        assert_eq!(
+            pane_command(&pane("bash", "", "/home/me/dev/dmux"), None),
+            ""
+        );
+    }
+
+    #[test]
+    fn relative_to_only_replaces_whole_path_components() {
+        let root = "/srv/app";
+        assert_eq!(
+            relative_to("cat /srv/app/x /srv/app2/x /srv/application", root, "."),
+            "cat x /srv/app2/x /srv/application"
+        );
+        assert_eq!(relative_to("ls '/srv/app'", root, "."), "ls '.'");
+        assert_eq!(relative_to("ls /x/srv/app/y", root, "."), "ls /x/srv/app/y");
+        assert_eq!(relative_to("ls /srv/app/y", root, ".."), "ls ../y");
+    }
+
+    #[test]
+    fn describe_cell_attaches_panes_to_leaves() {
+        let layout: Layout = LAYOUT.parse().unwrap();
+        let tree = describe_cell(&layout.root, &panes());
+        assert_eq!(tree.split, Some(Direction::Horizontal));
+        assert_eq!(tree.panes[0].command.as_deref(), Some("nvim"));
+        assert_eq!(tree.panes[1].index, Some(1));
+        assert_eq!(tree.panes[1].path, Some(PathBuf::from("/home/me/dev/dmux")));
+    }
+
+    #[test]
+    fn description_renders_in_every_structured_format() {
+        let description = Description {
+            windows: vec![describe_window(window(), &panes()).unwrap()],
+            profiles: BTreeMap::from([("dmux".to_owned(), profile_for(&window(), &panes()))]),
+        };
+        for (format, config_format) in [
+            (OutputFormat::Json, ConfigFormat::Json),
+            (OutputFormat::Toml, ConfigFormat::Toml),
+            (OutputFormat::Yaml, ConfigFormat::Yaml),
+        ] {
+            let rendered = render(&description, format).unwrap();
+            assert!(rendered.contains(LAYOUT), "{}", rendered);
+            // the profile is a top level table, ready to paste into the config
+            let value = config_format.parse(&rendered).unwrap();
+            assert_eq!(value["dmux"]["commands"][0], "cd src && nvim");
+            assert!(value.get("windows").is_none(), "{}", rendered);
+            assert_eq!(value["tmux_windows"][0]["name"], "dmux");
+        }
+        assert!(render(&description, OutputFormat::Raw).is_err());
+    }
+
+    #[test]
+    fn profiles_are_named_after_their_windows() {
+        let mut profiles = BTreeMap::new();
+        assert_eq!(profile_name(&window(), &profiles), "dmux");
+        profiles.insert("dmux".to_owned(), profile_for(&window(), &panes()));
+        assert_eq!(profile_name(&window(), &profiles), "dmux-2");
+        let mut windows = window();
+        windows.name = "windows".to_owned();
+        assert_eq!(profile_name(&windows, &profiles), "windows-2");
+        windows.name = "tmux_windows".to_owned();
+        assert_eq!(profile_name(&windows, &profiles), "tmux_windows-2");
+    }
+
+    #[test]
+    fn selected_windows_says_why_nothing_was_found() {
+        let mut recorder = Recorder::with_session("dev", &[]);
+        let err = selected_windows(&mut recorder, Some("dev"), None, false).unwrap_err();
+        assert_eq!(err.to_string(), "Session \"dev\" has no active window");
+        let err = selected_windows(&mut recorder, None, None, true).unwrap_err();
+        assert_eq!(err.to_string(), "There are no windows to describe");
+        let err = selected_windows(&mut recorder, None, Some("logs"), false).unwrap_err();
+        assert_eq!(err.to_string(), "No window named \"logs\" found");
+    }
+}
@@ -0,0 +1,213 @@
+// Writes a workspace out as a POSIX shell script of plain tmux calls,
+// so it can be opened where dmux isn't installed
+
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::backend::{shell_quote, Recorder, TmuxBackend};
+use crate::describe::root_positions;
+use crate::tmux::{self, WorkSpace};
+
+// Quotes an argument, pointing paths inside the workspace at `$dir`
+// so the script can be run on another directory. Paths in the middle of
+// an argument, like in a pane's command, are spliced in between quotes
+fn script_arg(arg: &str, root: &str) -> String {
+    if arg == root {
+        return "\"$dir\"".to_string();
+    }
+    if let Some(rest) = arg
+        .strip_prefix(root)
+        .and_then(|rest| rest.strip_prefix('/'))
+    {
+        return format!("\"$dir\"/{}", shell_quote(rest));
+    }
+    let mut quoted = String::new();
+    let mut end = 0;
+    for start in root_positions(arg, root) {
+        if start > end {
+            quoted.push_str(&shell_quote(&arg[end..start]));
+        }
+        quoted.push_str("\"$dir\"");
+        end = start + root.len();
+    }
+    if end < arg.len() || quoted.is_empty() {
+        quoted.push_str(&shell_quote(&arg[end..]));
+    }
+    quoted
+}
+
+fn tmux_line(tmux: &[String], args: &[String], root: &str) -> String {
+    tmux.iter()
+        .chain(args)
+        .map(|arg| script_arg(arg, root))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Like `tmux_line`, except that the time a window is tagged as
+// opened at is taken when the script runs
+fn command_line(tmux: &[String], command: &[String], root: &str) -> String {
+    match command {
+        [tag @ .., option, _] if option == tmux::CREATED_OPTION => {
+            format!("{} {} \"$(date +%s)\"", tmux_line(tmux, tag, root), option)
+        }
+        _ => tmux_line(tmux, command, root),
+    }
+}
+
+pub fn script(workspace: &WorkSpace, profile: &str, backend: &TmuxBackend) -> Result<String> {
+    let root = workspace
+        .path
+        .to_str()
+        .ok_or_else(|| anyhow!("{:?} isn't valid unicode", workspace.path))?;
+    let dir_name = Path::new(root)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(root);
+
+    // the script checks for the session itself, so nothing is asked of
+    // tmux and the workspace is recorded as if none of it existed yet
+    let mut recorder = Recorder {
+        server_args: backend.server_args(),
+        ..Recorder::default()
+    };
+    let start_window = tmux::create_workspace(workspace, &mut recorder)?;
+    let target = workspace.target_session(start_window, None)?;
+
+    let mut tmux = vec!["tmux".to_string()];
+    tmux.extend(recorder.server_args.iter().cloned());
+    let tmux_command = |args: &[&str]| {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        tmux_line(&tmux, &args, root)
+    };
+
+    let mut lines = vec![
+        "#!/bin/sh".to_string(),
+        format!(
+            "# Opens the {} dmux profile in a directory, {} unless another one is given",
+            profile, dir_name
+        ),
+        "set -e".to_string(),
+        String::new(),
+        format!("dir=${{1:-{}}}", shell_quote(root)),
+        String::new(),
+        format!(
+            "if {} 2>/dev/null; then",
+            tmux_command(&["has-session", "-t", &format!("={}", workspace.session_name)])
+        ),
+        format!(
+            "    echo {} >&2",
+            shell_quote(&format!(
+                "tmux session {} already exists",
+                workspace.session_name
+            ))
+        ),
+        "    exit 1".to_string(),
+        "fi".to_string(),
+        String::new(),
+    ];
+    lines.extend(
+        recorder
+            .commands
+            .iter()
+            .map(|command| command_line(&tmux, command, root)),
+    );
+    lines.extend([
+        String::new(),
+        "if [ -n \"$TMUX\" ]; then".to_string(),
+        format!("    {}", tmux_command(&["switch-client", "-t", &target])),
+        "else".to_string(),
+        format!("    {}", tmux_command(&["attach-session", "-t", &target])),
+        "fi".to_string(),
+    ]);
+    Ok(lines.join("\n") + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tmux::{Pane, Window};
+    use std::path::PathBuf;
+
+    fn workspace() -> WorkSpace {
+        WorkSpace {
+            path: PathBuf::from("/home/me/dev/app"),
+            session_name: "dev".to_owned(),
+            profile: "js".to_owned(),
+            windows: vec![Window {
+                name: None,
+                layout: "even-horizontal".parse().unwrap(),
+                panes: vec![
+                    Pane::from("nvim".to_owned()),
+                    Pane {
+                        command: "yarn watch".to_owned(),
+                        dir: Some(PathBuf::from("web")),
+                        ..Pane::default()
+                    },
+                ],
+                number_of_panes: 2,
+                main_pane_width: None,
+                main_pane_height: None,
+            }],
+            start_window: None,
+        }
+    }
+
+    #[test]
+    fn script_arg_points_workspace_paths_at_dir() {
+        assert_eq!(
+            script_arg("/home/me/dev/app", "/home/me/dev/app"),
+            "\"$dir\""
+        );
+        assert_eq!(
+            script_arg("/home/me/dev/app/my web", "/home/me/dev/app"),
+            "\"$dir\"/'my web'"
+        );
+        assert_eq!(
+            script_arg("/home/me/dev/application", "/home/me/dev/app"),
+            "/home/me/dev/application"
+        );
+        assert_eq!(
+            script_arg("cd /home/me/dev/app/web && make", "/home/me/dev/app"),
+            "'cd '\"$dir\"'/web && make'"
+        );
+    }
+
+    #[test]
+    fn script_keeps_custom_layouts_at_their_own_size() {
+        let mut workspace = workspace();
+        workspace.windows[0].layout = "34ed,230x56,0,0{132x56,0,0,3,97x56,133,0,222}"
+            .parse()
+            .unwrap();
+        let script = script(&workspace, "js", &TmuxBackend::default()).unwrap();
+        for line in [
+            "tmux new-session -d -s dev -n app -c \"$dir\" -x 230 -y 56",
+            "tmux select-layout -t dev:app.0 '34ed,230x56,0,0{132x56,0,0,3,97x56,133,0,222}'",
+        ] {
+            assert!(script.lines().any(|l| l == line), "{}\n{}", line, script);
+        }
+    }
+
+    #[test]
+    fn script_rebuilds_the_workspace_with_tmux_calls() {
+        let script = script(&workspace(), "js", &TmuxBackend::default()).unwrap();
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains("dir=${1:-/home/me/dev/app}\n"));
+        assert!(script.contains("if tmux has-session -t =dev 2>/dev/null; then\n"));
+        for line in [
+            "tmux new-session -d -s dev -n app -c \"$dir\"",
+            "tmux split-window -t dev:app -c \"$dir\"/web",
+            "tmux set-window-option -t dev:app @dmux_root \"$dir\"",
+            "tmux set-window-option -t dev:app @dmux_created \"$(date +%s)\"",
+            "tmux select-layout -t dev:app.0 even-horizontal",
+            "tmux send-keys -t dev:app.1 'yarn watch' Enter",
+            "tmux select-window -t dev:app",
+            "    tmux attach-session -t dev:app",
+        ] {
+            assert!(script.lines().any(|l| l == line), "{}\n{}", line, script);
+        }
+        // attaching is decided when the script runs
+        assert!(!script.lines().any(|l| l.starts_with("tmux switch-client")));
+    }
+}
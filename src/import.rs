@@ -0,0 +1,831 @@
+// Turns tmuxinator and tmuxp project files into dmux profiles for `dmux import`
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use colored::*;
+use serde_yaml::{Mapping, Value};
+
+use crate::app::ImportArgs;
+use crate::config_file::{self, expand_home};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    Tmuxinator,
+    Tmuxp,
+}
+
+impl FromStr for ImportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> Result<Self> {
+        match format {
+            "tmuxinator" => Ok(ImportFormat::Tmuxinator),
+            "tmuxp" => Ok(ImportFormat::Tmuxp),
+            _ => Err(anyhow!("unknown project file format {:?}", format)),
+        }
+    }
+}
+
+// Plain values come before tables in every struct here, TOML can't have them after
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Profile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_window: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    socket_name: Option<String>,
+    windows: Vec<Window>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct Window {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    layout: String,
+    number_of_panes: u8,
+    commands: Vec<PaneEntry>,
+}
+
+// Panes are written as plain commands unless one of the window's panes needs
+// a table, TOML arrays can't mix the two
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(untagged)]
+enum PaneEntry {
+    Command(String),
+    Table(Pane),
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+struct Pane {
+    command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dir: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    focus: bool,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
+}
+
+impl Pane {
+    fn is_plain(&self) -> bool {
+        *self
+            == Pane {
+                command: self.command.clone(),
+                ..Pane::default()
+            }
+    }
+}
+
+// A window as read from the project file, before it's written out as a profile's window
+struct ProjectWindow {
+    name: Option<String>,
+    layout: Option<String>,
+    panes: Vec<Pane>,
+}
+
+// tmux fits any number of panes into tiled, which is close to what
+// both tools do for windows without a layout
+const DEFAULT_LAYOUT: &str = "tiled";
+
+impl ProjectWindow {
+    fn into_window(self) -> Window {
+        let panes = if self.panes.is_empty() {
+            vec![Pane::default()]
+        } else {
+            self.panes
+        };
+        let commands: Vec<PaneEntry> = if panes.iter().all(Pane::is_plain) {
+            panes
+                .into_iter()
+                .map(|pane| PaneEntry::Command(pane.command))
+                .collect()
+        } else {
+            panes.into_iter().map(PaneEntry::Table).collect()
+        };
+        Window {
+            name: self.name,
+            layout: self.layout.unwrap_or_else(|| DEFAULT_LAYOUT.to_string()),
+            number_of_panes: commands.len() as u8,
+            commands,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Imported {
+    // the session name the project file gives, if any
+    pub name: Option<String>,
+    // the directory the project was tied to, which the profile no longer is
+    pub root: Option<PathBuf>,
+    pub profile: Profile,
+    // everything in the file dmux couldn't express
+    pub warnings: Vec<String>,
+}
+
+// Numbers and booleans show up where strings are meant, e.g. `1: vim` as a window
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(string) => Some(string.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        Value::Bool(boolean) => Some(boolean.to_string()),
+        _ => None,
+    }
+}
+
+fn get<'a>(map: &'a Mapping, key: &str) -> Option<&'a Value> {
+    map.get(&Value::String(key.to_string()))
+        .filter(|value| !value.is_null())
+}
+
+// The only key of a table and its value, which is how tmuxinator names windows and panes
+fn single_entry(value: &Value) -> Option<(&Value, &Value)> {
+    match value.as_mapping() {
+        Some(map) if map.len() == 1 => map.iter().next(),
+        _ => None,
+    }
+}
+
+fn join_commands(commands: Vec<String>) -> String {
+    commands
+        .into_iter()
+        .filter(|command| !command.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+struct Importer {
+    root: Option<PathBuf>,
+    warnings: Vec<String>,
+}
+
+impl Importer {
+    fn new(root: Option<&Value>) -> Importer {
+        Importer {
+            root: root.and_then(scalar).map(|root| expand_home(&root)),
+            warnings: vec![],
+        }
+    }
+
+    fn warn(&mut self, warning: String) {
+        self.warnings.push(warning);
+    }
+
+    // Warns about every key of a table that isn't one of the handled ones
+    fn leftover_keys(&mut self, map: &Mapping, handled: &[&str], place: &str) {
+        for (key, _) in map {
+            let key = scalar(key).unwrap_or_default();
+            if !handled.contains(&key.as_str()) {
+                self.warn(format!(
+                    "{}: `{}` can't be expressed in dmux and was left out",
+                    place, key
+                ));
+            }
+        }
+    }
+
+    // A command, or a list of them run one after the other
+    fn commands(&mut self, value: Option<&Value>, place: &str) -> Vec<String> {
+        match value {
+            None | Some(Value::Null) => vec![],
+            Some(Value::Sequence(items)) => items
+                .iter()
+                .flat_map(|item| self.commands(Some(item), place))
+                .collect(),
+            // tmuxp's `{ cmd: ..., enter: false }`
+            Some(Value::Mapping(map)) if get(map, "cmd").is_some() => {
+                self.leftover_keys(map, &["cmd"], place);
+                self.commands(get(map, "cmd"), place)
+            }
+            Some(other) => match scalar(other) {
+                Some(command) => vec![command],
+                None => {
+                    self.warn(format!("{}: couldn't read {:?} as a command", place, other));
+                    vec![]
+                }
+            },
+        }
+    }
+
+    // Where a window or pane starts, relative to the directory the profile is opened in.
+    // Relative dirs are relative to the dir of what they're in, or to the project root
+    fn dir(&mut self, dir: Option<&Value>, parent: Option<&Path>, place: &str) -> Option<PathBuf> {
+        let dir = match dir.and_then(scalar) {
+            Some(dir) => expand_home(&dir),
+            None => return parent.map(Path::to_path_buf),
+        };
+        let dir = match parent {
+            Some(parent) if dir.is_relative() => parent.join(dir),
+            _ => dir,
+        };
+        let dir = match &self.root {
+            Some(root) if dir.is_absolute() => match dir.strip_prefix(root) {
+                Ok(relative) => relative.to_path_buf(),
+                Err(_) => {
+                    self.warn(format!(
+                        "{}: {} is outside the project root, so it's used wherever the profile is opened",
+                        place,
+                        dir.display()
+                    ));
+                    return Some(dir);
+                }
+            },
+            _ => dir,
+        };
+        let dir: PathBuf = dir
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .collect();
+        Some(dir).filter(|dir| !dir.as_os_str().is_empty())
+    }
+
+    fn env(&mut self, value: Option<&Value>, place: &str) -> BTreeMap<String, String> {
+        let mut env = BTreeMap::new();
+        match value {
+            None => {}
+            Some(Value::Mapping(map)) => {
+                for (key, value) in map {
+                    match (scalar(key), scalar(value)) {
+                        (Some(key), Some(value)) => {
+                            env.insert(key, value);
+                        }
+                        _ => self.warn(format!("{}: couldn't read {:?} as a variable", place, key)),
+                    }
+                }
+            }
+            Some(other) => self.warn(format!(
+                "{}: couldn't read {:?} as environment variables",
+                place, other
+            )),
+        }
+        env
+    }
+}
+
+// The windows of a project, failing when there aren't any
+fn project_windows<'a>(doc: &'a Mapping, keys: &[&str]) -> Result<&'a Vec<Value>> {
+    keys.iter()
+        .find_map(|key| get(doc, key))
+        .and_then(Value::as_sequence)
+        .filter(|windows| !windows.is_empty())
+        .ok_or_else(|| anyhow!("The project doesn't list any windows"))
+}
+
+fn tmuxinator(doc: &Mapping) -> Result<Imported> {
+    let mut importer = Importer::new(get(doc, "root").or_else(|| get(doc, "project_root")));
+    importer.leftover_keys(
+        doc,
+        &[
+            "name",
+            "project_name",
+            "root",
+            "project_root",
+            "socket_name",
+            "pre_window",
+            "pre_tab",
+            "windows",
+            "tabs",
+            "startup_window",
+            "startup_pane",
+        ],
+        "project",
+    );
+    let pre_window = importer.commands(
+        get(doc, "pre_window").or_else(|| get(doc, "pre_tab")),
+        "pre_window",
+    );
+
+    let mut windows = vec![];
+    for (index, entry) in project_windows(doc, &["windows", "tabs"])?
+        .iter()
+        .enumerate()
+    {
+        let (name, value) = match single_entry(entry) {
+            Some((name, value)) => (scalar(name), value),
+            None => {
+                importer.warn(format!("window {}: couldn't read {:?}", index, entry));
+                continue;
+            }
+        };
+        let place = format!(
+            "window {}",
+            name.clone().unwrap_or_else(|| index.to_string())
+        );
+        let window = match value {
+            Value::Mapping(options) => {
+                importer.leftover_keys(options, &["layout", "root", "pre", "panes"], &place);
+                let dir = importer.dir(get(options, "root"), None, &place);
+                let mut before = pre_window.clone();
+                before.extend(importer.commands(get(options, "pre"), &place));
+                let panes = get(options, "panes")
+                    .and_then(Value::as_sequence)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                // a window without panes still starts in its root and runs its pre commands
+                let panes: &[Value] = if panes.is_empty() {
+                    &[Value::Null]
+                } else {
+                    panes
+                };
+                ProjectWindow {
+                    name,
+                    layout: get(options, "layout").and_then(scalar),
+                    panes: panes
+                        .iter()
+                        .map(|pane| {
+                            // a pane can be a table with its title as the only key
+                            let (title, commands) = match single_entry(pane) {
+                                Some((title, commands)) => (scalar(title), commands),
+                                None => (None, pane),
+                            };
+                            let mut commands = importer.commands(Some(commands), &place);
+                            commands.splice(0..0, before.clone());
+                            Pane {
+                                command: join_commands(commands),
+                                dir: dir.clone(),
+                                title,
+                                ..Pane::default()
+                            }
+                        })
+                        .collect(),
+                }
+            }
+            // a command or a list of them for a window with one pane
+            commands => {
+                let mut commands = importer.commands(Some(commands), &place);
+                commands.splice(0..0, pre_window.clone());
+                ProjectWindow {
+                    name,
+                    layout: None,
+                    panes: vec![Pane {
+                        command: join_commands(commands),
+                        ..Pane::default()
+                    }],
+                }
+            }
+        };
+        windows.push(window);
+    }
+
+    // tmuxinator takes the name or the index of the window
+    let start_index = match get(doc, "startup_window").and_then(scalar) {
+        None => None,
+        Some(start) => {
+            let index = windows
+                .iter()
+                .position(|window| window.name.as_deref() == Some(start.as_str()))
+                .or_else(|| start.parse().ok())
+                .filter(|index| *index < windows.len());
+            if index.is_none() {
+                importer.warn(format!(
+                    "startup_window {} isn't one of the windows, the profile starts in the first one",
+                    start
+                ));
+            }
+            index
+        }
+    };
+    if let Some(pane) = get(doc, "startup_pane").and_then(scalar) {
+        let window = windows.get_mut(start_index.unwrap_or(0));
+        match (window, pane.parse::<usize>()) {
+            (Some(window), Ok(index)) if index < window.panes.len() => {
+                window.panes[index].focus = true
+            }
+            _ => importer.warn(format!(
+                "startup_pane {} isn't one of the panes of the start window",
+                pane
+            )),
+        }
+    }
+
+    let name = get(doc, "name")
+        .or_else(|| get(doc, "project_name"))
+        .and_then(scalar);
+    Ok(Imported {
+        profile: Profile {
+            session_name: name.clone(),
+            start_window: start_window(&windows, start_index, &mut importer),
+            socket_name: get(doc, "socket_name").and_then(scalar),
+            windows: windows
+                .into_iter()
+                .map(ProjectWindow::into_window)
+                .collect(),
+        },
+        name,
+        root: importer.root,
+        warnings: importer.warnings,
+    })
+}
+
+fn tmuxp(doc: &Mapping) -> Result<Imported> {
+    let mut importer = Importer::new(get(doc, "start_directory"));
+    importer.leftover_keys(
+        doc,
+        &[
+            "session_name",
+            "start_directory",
+            "shell_command_before",
+            "environment",
+            "windows",
+        ],
+        "session",
+    );
+    let session_before = importer.commands(get(doc, "shell_command_before"), "session");
+    let session_env = importer.env(get(doc, "environment"), "session");
+
+    let mut windows = vec![];
+    let mut start_index = None;
+    for (index, entry) in project_windows(doc, &["windows"])?.iter().enumerate() {
+        let options = match entry.as_mapping() {
+            Some(options) => options,
+            None => {
+                importer.warn(format!("window {}: couldn't read {:?}", index, entry));
+                continue;
+            }
+        };
+        let name = get(options, "window_name").and_then(scalar);
+        let place = format!(
+            "window {}",
+            name.clone().unwrap_or_else(|| index.to_string())
+        );
+        importer.leftover_keys(
+            options,
+            &[
+                "window_name",
+                "layout",
+                "start_directory",
+                "shell_command_before",
+                "environment",
+                "focus",
+                "panes",
+            ],
+            &place,
+        );
+        if get(options, "focus").and_then(Value::as_bool) == Some(true) {
+            start_index = Some(windows.len());
+        }
+        let dir = importer.dir(get(options, "start_directory"), None, &place);
+        let mut before = session_before.clone();
+        before.extend(importer.commands(get(options, "shell_command_before"), &place));
+        let mut env = session_env.clone();
+        env.extend(importer.env(get(options, "environment"), &place));
+
+        let panes = get(options, "panes")
+            .and_then(Value::as_sequence)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let mut window = ProjectWindow {
+            name,
+            layout: get(options, "layout").and_then(scalar),
+            panes: vec![],
+        };
+        for (pane_index, pane) in panes.iter().enumerate() {
+            let place = format!("{} pane {}", place, pane_index);
+            let mut commands = before.clone();
+            let mut pane_env = env.clone();
+            let mut pane_dir = dir.clone();
+            let mut focus = false;
+            match pane {
+                Value::Mapping(pane) => {
+                    importer.leftover_keys(
+                        pane,
+                        &[
+                            "shell_command",
+                            "shell_command_before",
+                            "start_directory",
+                            "environment",
+                            "focus",
+                        ],
+                        &place,
+                    );
+                    commands.extend(importer.commands(get(pane, "shell_command_before"), &place));
+                    commands.extend(importer.commands(get(pane, "shell_command"), &place));
+                    pane_env.extend(importer.env(get(pane, "environment"), &place));
+                    pane_dir = importer.dir(get(pane, "start_directory"), dir.as_deref(), &place);
+                    focus = get(pane, "focus").and_then(Value::as_bool) == Some(true);
+                }
+                // tmuxp's shorthand for an empty pane
+                Value::String(blank) if blank == "blank" || blank == "pane" => {}
+                other => commands.extend(importer.commands(Some(other), &place)),
+            }
+            window.panes.push(Pane {
+                command: join_commands(commands),
+                dir: pane_dir,
+                env: pane_env,
+                focus,
+                ..Pane::default()
+            });
+        }
+        // a window without panes still starts in its dir and runs its commands
+        if window.panes.is_empty() {
+            window.panes.push(Pane {
+                command: join_commands(before),
+                dir,
+                env,
+                ..Pane::default()
+            });
+        }
+        windows.push(window);
+    }
+
+    let name = get(doc, "session_name").and_then(scalar);
+    Ok(Imported {
+        profile: Profile {
+            session_name: name.clone(),
+            start_window: start_window(&windows, start_index, &mut importer),
+            socket_name: None,
+            windows: windows
+                .into_iter()
+                .map(ProjectWindow::into_window)
+                .collect(),
+        },
+        name,
+        root: importer.root,
+        warnings: importer.warnings,
+    })
+}
+
+// dmux finds the start window by name, so it can't be one without a name
+fn start_window(
+    windows: &[ProjectWindow],
+    index: Option<usize>,
+    importer: &mut Importer,
+) -> Option<String> {
+    let window = &windows[index.filter(|index| *index > 0)?];
+    if window.name.is_none() {
+        importer.warn(format!(
+            "window {} can't be the start window without a name, the profile starts in the first one",
+            index.unwrap_or_default()
+        ));
+    }
+    window.name.clone()
+}
+
+pub fn parse(contents: &str, format: ImportFormat) -> Result<Imported> {
+    let doc: Value = serde_yaml::from_str(contents).context("Couldn't read the project file")?;
+    let doc = doc
+        .as_mapping()
+        .ok_or_else(|| anyhow!("The project file isn't a table of settings"))?;
+    let mut imported = match format {
+        ImportFormat::Tmuxinator => tmuxinator(doc)?,
+        ImportFormat::Tmuxp => tmuxp(doc)?,
+    };
+    if format == ImportFormat::Tmuxinator && contents.contains("<%") {
+        imported.warnings.push(
+            "the project uses ERB, which was imported as it's written rather than run".to_string(),
+        );
+    }
+    Ok(imported)
+}
+
+pub fn import(args: ImportArgs) -> Result<()> {
+    let contents =
+        fs::read_to_string(&args.file).with_context(|| format!("Couldn't read {:?}", args.file))?;
+    let imported = parse(&contents, args.format)
+        .with_context(|| format!("Couldn't import {:?}", args.file))?;
+    let name = args
+        .name
+        .or(imported.name)
+        .or_else(|| {
+            args.file
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        })
+        .ok_or_else(|| anyhow!("The project has no name, give the profile one with --name"))?;
+    for warning in &imported.warnings {
+        eprintln!("{}: {}", "Warning".yellow(), warning);
+    }
+    let path = config_file::add_profile(&name, &imported.profile)?;
+    let dir = imported
+        .root
+        .map(|root| root.display().to_string())
+        .unwrap_or_else(|| "<dir>".to_string());
+    println!(
+        "Added profile {:?} to {}, open it with `dmux -P {} {}`",
+        name,
+        path.display(),
+        name,
+        dir
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::WorkSpaceArgs;
+
+    const TMUXINATOR: &str = r#"
+name: shop
+root: /home/me/dev/shop
+pre_window: nvm use
+on_project_start: docker compose up -d
+startup_window: server
+startup_pane: 1
+windows:
+  - editor:
+      layout: main-vertical
+      panes:
+        - vim
+        - logs: tail -f log/development.log
+  - server:
+      root: /home/me/dev/shop/api
+      synchronize: after
+      panes:
+        - [bundle install, rails s]
+        -
+  - console: rails c
+  - scratch:
+"#;
+
+    const TMUXP: &str = r#"
+session_name: shop
+start_directory: /home/me/dev/shop
+shell_command_before: nvm use
+environment:
+  RAILS_ENV: development
+before_script: ./bootstrap.sh
+windows:
+  - window_name: editor
+    panes:
+      - vim
+      - blank
+  - window_name: api
+    layout: even-horizontal
+    start_directory: api
+    focus: true
+    options:
+      automatic-rename: on
+    panes:
+      - shell_command:
+          - bundle install
+          - cmd: rails s
+        focus: true
+      - start_directory: /srv/logs
+        shell_command: tail -f api.log
+"#;
+
+    fn to_toml(profile: &Profile) -> String {
+        toml::to_string(profile).unwrap()
+    }
+
+    #[test]
+    fn imports_tmuxinator_windows_panes_and_pre_window() {
+        let imported = parse(TMUXINATOR, ImportFormat::Tmuxinator).unwrap();
+        assert_eq!(imported.name.as_deref(), Some("shop"));
+        assert_eq!(imported.root, Some(PathBuf::from("/home/me/dev/shop")));
+        let profile = imported.profile;
+        assert_eq!(profile.start_window.as_deref(), Some("server"));
+        assert_eq!(
+            profile.windows[0],
+            Window {
+                name: Some("editor".to_owned()),
+                layout: "main-vertical".to_owned(),
+                number_of_panes: 2,
+                commands: vec![
+                    PaneEntry::Table(Pane {
+                        command: "nvm use; vim".to_owned(),
+                        ..Pane::default()
+                    }),
+                    PaneEntry::Table(Pane {
+                        command: "nvm use; tail -f log/development.log".to_owned(),
+                        title: Some("logs".to_owned()),
+                        ..Pane::default()
+                    }),
+                ],
+            }
+        );
+        // the window's root is made relative to the project's
+        assert_eq!(
+            profile.windows[1].commands[1],
+            PaneEntry::Table(Pane {
+                command: "nvm use".to_owned(),
+                dir: Some(PathBuf::from("api")),
+                focus: true,
+                ..Pane::default()
+            })
+        );
+        assert_eq!(
+            profile.windows[2].commands,
+            vec![PaneEntry::Command("nvm use; rails c".to_owned())]
+        );
+        assert_eq!(profile.windows[3].layout, "tiled");
+        assert_eq!(
+            imported.warnings,
+            vec![
+                "project: `on_project_start` can't be expressed in dmux and was left out",
+                "window server: `synchronize` can't be expressed in dmux and was left out",
+            ]
+        );
+    }
+
+    #[test]
+    fn imports_tmuxp_windows_dirs_and_environment() {
+        let imported = parse(TMUXP, ImportFormat::Tmuxp).unwrap();
+        let profile = imported.profile;
+        assert_eq!(profile.session_name.as_deref(), Some("shop"));
+        assert_eq!(profile.start_window.as_deref(), Some("api"));
+        let env: BTreeMap<String, String> = [("RAILS_ENV".to_owned(), "development".to_owned())]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            profile.windows[0].commands[1],
+            PaneEntry::Table(Pane {
+                command: "nvm use".to_owned(),
+                env: env.clone(),
+                ..Pane::default()
+            })
+        );
+        assert_eq!(
+            profile.windows[1].commands,
+            vec![
+                PaneEntry::Table(Pane {
+                    command: "nvm use; bundle install; rails s".to_owned(),
+                    dir: Some(PathBuf::from("api")),
+                    focus: true,
+                    env: env.clone(),
+                    ..Pane::default()
+                }),
+                PaneEntry::Table(Pane {
+                    command: "nvm use; tail -f api.log".to_owned(),
+                    dir: Some(PathBuf::from("/srv/logs")),
+                    env,
+                    ..Pane::default()
+                }),
+            ]
+        );
+        assert_eq!(
+            imported.warnings,
+            vec![
+                "session: `before_script` can't be expressed in dmux and was left out",
+                "window api: `options` can't be expressed in dmux and was left out",
+                "window api pane 1: /srv/logs is outside the project root, so it's used wherever the profile is opened",
+            ]
+        );
+    }
+
+    #[test]
+    fn windows_without_panes_keep_their_dir_and_commands() {
+        let tmuxinator = "root: /srv/shop\n\
+                          pre_window: nvm use\n\
+                          windows:\n  \
+                          - api:\n      \
+                          root: api\n      \
+                          pre: bundle install\n";
+        let profile = parse(tmuxinator, ImportFormat::Tmuxinator).unwrap().profile;
+        assert_eq!(
+            profile.windows[0].commands,
+            vec![PaneEntry::Table(Pane {
+                command: "nvm use; bundle install".to_owned(),
+                dir: Some(PathBuf::from("api")),
+                ..Pane::default()
+            })]
+        );
+
+        let tmuxp = "session_name: shop\n\
+                     start_directory: /srv/shop\n\
+                     windows:\n  \
+                     - window_name: api\n    \
+                     start_directory: api\n    \
+                     shell_command_before: bundle install\n";
+        let profile = parse(tmuxp, ImportFormat::Tmuxp).unwrap().profile;
+        assert_eq!(
+            profile.windows[0].commands,
+            vec![PaneEntry::Table(Pane {
+                command: "bundle install".to_owned(),
+                dir: Some(PathBuf::from("api")),
+                ..Pane::default()
+            })]
+        );
+    }
+
+    #[test]
+    fn imported_profiles_read_back_as_workspaces() {
+        for (contents, format) in [
+            (TMUXINATOR, ImportFormat::Tmuxinator),
+            (TMUXP, ImportFormat::Tmuxp),
+        ] {
+            let toml = to_toml(&parse(contents, format).unwrap().profile);
+            let args: WorkSpaceArgs = toml::from_str(&toml).unwrap();
+            let windows = args.to_windows().unwrap();
+            assert_eq!(windows.len(), args.windows.len(), "{}", toml);
+            assert!(windows.iter().any(|window| window
+                .panes
+                .iter()
+                .any(|pane| pane.dir == Some(PathBuf::from("api")))));
+        }
+    }
+
+    #[test]
+    fn projects_without_windows_are_refused() {
+        let err = parse("name: empty\nroot: ~/dev\n", ImportFormat::Tmuxinator).unwrap_err();
+        assert_eq!(err.to_string(), "The project doesn't list any windows");
+        assert!(parse("- vim\n", ImportFormat::Tmuxp).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+// Resolves profiles that build on others with `extends`, e.g.
+// [rust]
+// extends = ["base", "wide"]
+// append_commands = ["cargo watch -x test"]
+//
+// The profiles a profile extends are merged in order, each one over the
+// ones before it, and the profile itself over all of them. Tables are
+// merged key by key, anything else is replaced. `prepend_commands` and
+// `append_commands` add to the inherited commands instead of replacing them
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use config::Value;
+
+type Table = HashMap<String, Value>;
+
+fn merge(parent: Value, child: Value) -> Value {
+    match (parent.clone().into_table(), child.clone().into_table()) {
+        (Ok(parent), Ok(child)) => Value::from(merge_tables(parent, child)),
+        _ => child,
+    }
+}
+
+fn merge_tables(mut parent: Table, child: Table) -> Table {
+    for (key, value) in child {
+        let value = match parent.remove(&key) {
+            Some(inherited) => merge(inherited, value),
+            None => value,
+        };
+        parent.insert(key, value);
+    }
+    parent
+}
+
+// `extends` is either one profile or a list of them
+fn parent_names(extends: Value) -> Result<Vec<String>> {
+    let names = match extends.clone().into_array() {
+        Ok(names) => names,
+        Err(_) => vec![extends],
+    };
+    names
+        .into_iter()
+        .map(|name| {
+            name.into_str()
+                .context("extends has to be a profile name or a list of them")
+        })
+        .collect()
+}
+
+fn commands(table: &mut Table, key: &str) -> Result<Vec<Value>> {
+    match table.remove(key) {
+        Some(commands) => commands
+            .into_array()
+            .with_context(|| format!("{} has to be a list", key)),
+        None => Ok(vec![]),
+    }
+}
+
+// `chain` holds the profiles being resolved, to catch profiles extending themselves
+fn resolve(settings: &config::Config, name: &str, chain: &mut Vec<String>) -> Result<Table> {
+    if chain.iter().any(|extending| extending == name) {
+        chain.push(name.to_string());
+        return Err(anyhow!(
+            "Profiles can't extend themselves, but {} does",
+            chain.join(" -> ")
+        ));
+    }
+    let mut table: Table = settings.get(name).with_context(|| match chain.last() {
+        Some(child) => format!(
+            "Profile {:?} extends {:?}, which couldn't be read",
+            child, name
+        ),
+        None => format!("Couldn't read profile {:?}", name),
+    })?;
+    let parents = match table.remove("extends") {
+        Some(extends) => {
+            parent_names(extends).with_context(|| format!("Invalid profile {:?}", name))?
+        }
+        None => vec![],
+    };
+    chain.push(name.to_string());
+    let mut resolved = Table::new();
+    for parent in parents {
+        resolved = merge_tables(resolved, resolve(settings, &parent, chain)?);
+    }
+    chain.pop();
+
+    let prepend = commands(&mut table, "prepend_commands")?;
+    let append = commands(&mut table, "append_commands")?;
+    let mut resolved = merge_tables(resolved, table);
+    if !prepend.is_empty() || !append.is_empty() {
+        let inherited = commands(&mut resolved, "commands")?;
+        let commands: Vec<Value> = prepend.into_iter().chain(inherited).chain(append).collect();
+        resolved.insert("commands".to_string(), Value::from(commands));
+    }
+    Ok(resolved)
+}
+
+// The settings of the profile with everything it extends merged in
+pub fn resolve_profile(settings: &config::Config, name: &str) -> Result<Table> {
+    resolve(settings, name, &mut vec![])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(contents: &str) -> config::Config {
+        let mut settings = config::Config::default();
+        settings
+            .merge(config::File::from_str(contents, config::FileFormat::Toml))
+            .unwrap();
+        settings
+    }
+
+    fn resolved(contents: &str, name: &str) -> Table {
+        resolve_profile(&settings(contents), name).unwrap()
+    }
+
+    fn string(table: &Table, key: &str) -> String {
+        table[key].clone().into_str().unwrap()
+    }
+
+    fn commands(table: &Table) -> Vec<String> {
+        table["commands"]
+            .clone()
+            .into_array()
+            .unwrap()
+            .into_iter()
+            .map(|command| command.into_str().unwrap())
+            .collect()
+    }
+
+    const CONFIG: &str = r#"
+[base]
+session_name = "work"
+layout = "tiled"
+commands = ["nvim", "git status"]
+
+[wide]
+layout = "even-horizontal"
+main_pane_width = "120"
+
+[rust]
+extends = ["base", "wide"]
+number_of_panes = 3
+append_commands = ["cargo watch -x test"]
+
+[docs]
+extends = "rust"
+prepend_commands = ["mdbook serve"]
+main_pane_width = "100"
+"#;
+
+    #[test]
+    fn profiles_inherit_from_the_ones_they_extend() {
+        let rust = resolved(CONFIG, "rust");
+        assert_eq!(string(&rust, "session_name"), "work");
+        assert_eq!(string(&rust, "number_of_panes"), "3");
+        // later parents win over earlier ones
+        assert_eq!(string(&rust, "layout"), "even-horizontal");
+        assert_eq!(string(&rust, "main_pane_width"), "120");
+        assert_eq!(
+            commands(&rust),
+            vec!["nvim", "git status", "cargo watch -x test"]
+        );
+        assert!(!rust.contains_key("extends") && !rust.contains_key("append_commands"));
+
+        let docs = resolved(CONFIG, "docs");
+        assert_eq!(string(&docs, "main_pane_width"), "100");
+        assert_eq!(
+            commands(&docs),
+            vec!["mdbook serve", "nvim", "git status", "cargo watch -x test"]
+        );
+    }
+
+    #[test]
+    fn own_commands_replace_inherited_ones() {
+        let config = format!(
+            "{}\n[notes]\nextends = \"base\"\ncommands = [\"glow\"]\n",
+            CONFIG
+        );
+        assert_eq!(commands(&resolved(&config, "notes")), vec!["glow"]);
+    }
+
+    #[test]
+    fn tables_are_merged_deeply() {
+        let config = r#"
+[base.layout]
+split = "horizontal"
+panes = [{ command = "nvim" }, {}]
+
+[child]
+extends = "base"
+layout = { split = "vertical" }
+"#;
+        let layout = resolved(config, "child")["layout"]
+            .clone()
+            .into_table()
+            .unwrap();
+        assert_eq!(string(&layout, "split"), "vertical");
+        assert_eq!(layout["panes"].clone().into_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn cycles_are_reported() {
+        let config = "[a]\nextends = \"b\"\n[b]\nextends = [\"c\"]\n[c]\nextends = \"a\"\n";
+        let err = resolve_profile(&settings(config), "a").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Profiles can't extend themselves, but a -> b -> c -> a does"
+        );
+        let err = resolve_profile(&settings("[a]\nextends = \"a\"\n"), "a").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Profiles can't extend themselves, but a -> a does"
+        );
+    }
+
+    #[test]
+    fn missing_parents_are_named() {
+        let err = resolve_profile(&settings("[a]\nextends = \"base\"\n"), "a").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Profile \"a\" extends \"base\", which couldn't be read"
+        );
+    }
+}
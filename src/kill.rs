@@ -0,0 +1,313 @@
+// Kills running dmux workspaces for `dmux kill`
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+
+use crate::app::KillArgs;
+use crate::backend::Backend;
+use crate::list::{self, display_dir};
+use crate::select;
+use crate::tmux::WindowInfo;
+
+// A session or window dmux opened
+#[derive(Debug, PartialEq, Eq)]
+enum Running {
+    Session {
+        name: String,
+        root: PathBuf,
+        windows: usize,
+        panes: u32,
+        attached: bool,
+    },
+    Window(WindowInfo),
+}
+
+impl Running {
+    fn target(&self) -> String {
+        match self {
+            Running::Session { name, .. } => name.clone(),
+            Running::Window(window) => format!("{}:{}", window.session, window.name),
+        }
+    }
+
+    fn session(&self) -> &str {
+        match self {
+            Running::Session { name, .. } => name,
+            Running::Window(window) => &window.session,
+        }
+    }
+
+    fn matches(&self, target: &str) -> bool {
+        match self {
+            Running::Session { name, .. } => name == target,
+            Running::Window(window) => {
+                target == self.target() || target == format!("{}:{}", window.session, window.index)
+            }
+        }
+    }
+
+    // A line for the picker, with the target padded to `width`
+    fn line(&self, width: usize) -> String {
+        let (kind, panes, attached, root) = match self {
+            Running::Session {
+                windows,
+                panes,
+                attached,
+                root,
+                ..
+            } => (
+                "session",
+                format!("{} windows, {} panes", windows, panes),
+                *attached,
+                root,
+            ),
+            Running::Window(window) => (
+                "window",
+                format!("{} panes", window.panes),
+                window.attached,
+                window
+                    .root
+                    .as_ref()
+                    .expect("only windows with a root are listed"),
+            ),
+        };
+        format!(
+            "{:width$}  {:7}  {:18}  {:8}  {}",
+            self.target(),
+            kind,
+            panes,
+            if attached { "attached" } else { "detached" },
+            display_dir(root),
+            width = width
+        )
+    }
+}
+
+// The sessions and windows dmux opened, a session being one with any window
+// dmux opened. Sessions are left out with `windows_only` and windows with `sessions_only`
+fn running(windows: Vec<WindowInfo>, sessions_only: bool, windows_only: bool) -> Vec<Running> {
+    let windows: Vec<WindowInfo> = windows
+        .into_iter()
+        .filter(|window| window.root.is_some())
+        .collect();
+    let mut running = vec![];
+    for window in &windows {
+        if windows_only
+            || running
+                .iter()
+                .any(|r: &Running| r.session() == window.session)
+        {
+            continue;
+        }
+        let session: Vec<&WindowInfo> = windows
+            .iter()
+            .filter(|other| other.session == window.session)
+            .collect();
+        running.push(Running::Session {
+            name: window.session.clone(),
+            root: window.root.clone().unwrap_or_default(),
+            windows: session.len(),
+            panes: session.iter().map(|window| window.panes).sum(),
+            attached: window.attached,
+        });
+    }
+    if !sessions_only {
+        running.extend(windows.into_iter().map(Running::Window));
+    }
+    running
+}
+
+// The running sessions and windows that were named, failing for any that isn't running
+fn named<'a>(running: &'a [Running], targets: &[String]) -> Result<Vec<&'a Running>> {
+    targets
+        .iter()
+        .map(|target| {
+            running
+                .iter()
+                .find(|running| running.matches(target))
+                .ok_or_else(|| anyhow!("{:?} isn't a running dmux session or window", target))
+        })
+        .collect()
+}
+
+fn picked(running: &[Running]) -> Result<Vec<&Running>> {
+    let width = running
+        .iter()
+        .map(|running| running.target().len())
+        .max()
+        .unwrap_or_default();
+    let lines: Vec<String> = running.iter().map(|running| running.line(width)).collect();
+    let picked = select::select_lines(&lines, "kill> ").context("Couldn't start fzf-tmux")?;
+    Ok(running
+        .iter()
+        .zip(&lines)
+        .filter(|(_, line)| picked.contains(line))
+        .map(|(running, _)| running)
+        .collect())
+}
+
+fn confirm(targets: &[String]) -> Result<bool> {
+    eprint!("Kill {}? [y/N] ", targets.join(", "));
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+// Kills the windows before the sessions, leaving out windows of sessions being killed
+fn kill_running(chosen: &[&Running], tmux: &mut dyn Backend) -> Result<Vec<String>> {
+    let sessions: Vec<&str> = chosen
+        .iter()
+        .filter(|running| matches!(running, Running::Session { .. }))
+        .map(|running| running.session())
+        .collect();
+    let mut windows: Vec<&WindowInfo> = chosen
+        .iter()
+        .filter_map(|running| match running {
+            Running::Window(window) if !sessions.contains(&window.session.as_str()) => Some(window),
+            _ => None,
+        })
+        .collect();
+    // the last windows go first, so with renumber-windows on the
+    // indexes of the others stay the same
+    windows.sort_by_key(|window| std::cmp::Reverse(window.index));
+    let mut killed = vec![];
+    for window in windows {
+        let name = format!("{}:{}", window.session, window.name);
+        tmux.kill_window(&format!("{}:{}", window.session, window.index))
+            .with_context(|| format!("Couldn't kill window {}", name))?;
+        killed.push(name);
+    }
+    for session in sessions {
+        tmux.kill_session(session)
+            .with_context(|| format!("Couldn't kill session {}", session))?;
+        killed.push(session.to_string());
+    }
+    tmux.flush()?;
+    Ok(killed)
+}
+
+pub fn kill(args: KillArgs, tmux: &mut dyn Backend) -> Result<()> {
+    let running = running(
+        list::server_windows(tmux)?,
+        args.sessions_only,
+        args.windows_only,
+    );
+    let chosen = if !args.targets.is_empty() {
+        named(&running, &args.targets)?
+    } else if running.is_empty() {
+        eprintln!("There are no dmux workspaces running, so there's nothing to kill");
+        return Ok(());
+    } else {
+        picked(&running)?
+    };
+    if chosen.is_empty() {
+        return Ok(());
+    }
+    let targets: Vec<String> = chosen.iter().map(|running| running.target()).collect();
+    if !args.yes && !confirm(&targets)? {
+        return Ok(());
+    }
+    for killed in kill_running(&chosen, tmux)? {
+        println!("Killed {}", killed);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Recorder;
+
+    fn window(session: &str, index: u32, name: &str, root: Option<&str>) -> WindowInfo {
+        WindowInfo {
+            session: session.to_owned(),
+            index,
+            name: name.to_owned(),
+            active: index == 0,
+            layout: String::new(),
+            panes: 2,
+            attached: session == "web",
+            root: root.map(PathBuf::from),
+            profile: None,
+            created: None,
+        }
+    }
+
+    fn windows() -> Vec<WindowInfo> {
+        vec![
+            window("web", 0, "editor", Some("/srv/web")),
+            window("web", 1, "zsh", None),
+            window("web", 2, "logs", Some("/srv/web")),
+            window("notes", 0, "zsh", None),
+            window("api", 0, "api", Some("/srv/api")),
+        ]
+    }
+
+    #[test]
+    fn running_lists_only_what_dmux_opened() {
+        let running = running(windows(), false, false);
+        let targets: Vec<String> = running.iter().map(Running::target).collect();
+        assert_eq!(
+            targets,
+            vec!["web", "api", "web:editor", "web:logs", "api:api"]
+        );
+        assert_eq!(
+            running[0],
+            Running::Session {
+                name: "web".to_owned(),
+                root: PathBuf::from("/srv/web"),
+                windows: 2,
+                panes: 4,
+                attached: true,
+            }
+        );
+        assert_eq!(
+            running[0].line(10),
+            "web         session  2 windows, 4 panes  attached  /srv/web"
+        );
+        assert_eq!(
+            running[4].line(10),
+            "api:api     window   2 panes             detached  /srv/api"
+        );
+    }
+
+    #[test]
+    fn running_filters_sessions_or_windows() {
+        assert_eq!(running(windows(), true, false).len(), 2);
+        assert!(running(windows(), false, true)
+            .iter()
+            .all(|running| matches!(running, Running::Window(_))));
+    }
+
+    #[test]
+    fn named_finds_windows_by_name_or_index() {
+        let running = running(windows(), false, false);
+        let targets = ["web:2".to_owned(), "api".to_owned()];
+        let chosen = named(&running, &targets).unwrap();
+        assert_eq!(chosen[0].target(), "web:logs");
+        assert_eq!(chosen[1].target(), "api");
+        // hand made windows can't be killed through dmux
+        assert!(named(&running, &["web:zsh".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn kill_running_skips_windows_of_killed_sessions() {
+        let running = running(windows(), false, false);
+        let targets = [
+            "web:editor".to_owned(),
+            "api:api".to_owned(),
+            "api".to_owned(),
+        ];
+        let chosen = named(&running, &targets).unwrap();
+        let mut recorder = Recorder::default();
+        let killed = kill_running(&chosen, &mut recorder).unwrap();
+        assert_eq!(killed, vec!["web:editor", "api"]);
+        assert_eq!(
+            recorder.command_lines(),
+            vec!["kill-window -t web:0", "kill-session -t api"]
+        );
+    }
+}
@@ -0,0 +1,705 @@
+// Parser and serializer for tmux layout strings.
+//
+// A layout string looks like
+// `5e09,281x67,0,0{133x67,0,0,17,147x67,134,0}`
+// where the leading 4 hex digits are a checksum of everything after the first comma.
+// `{}` holds cells laid out left to right, `[]` holds cells laid out top to bottom,
+// and a cell without children is a pane, optionally followed by its pane id.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    // `{...}` children are side by side
+    Horizontal,
+    // `[...]` children are stacked on top of each other
+    Vertical,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CellKind {
+    Pane(Option<u32>),
+    Split(Direction, Vec<Cell>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cell {
+    pub width: u32,
+    pub height: u32,
+    pub x: u32,
+    pub y: u32,
+    pub kind: CellKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub root: Cell,
+}
+
+// Layouts built into tmux, which work for any number of panes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    EvenHorizontal,
+    EvenVertical,
+    MainHorizontal,
+    MainHorizontalMirrored,
+    MainVertical,
+    MainVerticalMirrored,
+    Tiled,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 7] = [
+        Preset::EvenHorizontal,
+        Preset::EvenVertical,
+        Preset::MainHorizontal,
+        Preset::MainHorizontalMirrored,
+        Preset::MainVertical,
+        Preset::MainVerticalMirrored,
+        Preset::Tiled,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::EvenHorizontal => "even-horizontal",
+            Preset::EvenVertical => "even-vertical",
+            Preset::MainHorizontal => "main-horizontal",
+            Preset::MainHorizontalMirrored => "main-horizontal-mirrored",
+            Preset::MainVertical => "main-vertical",
+            Preset::MainVerticalMirrored => "main-vertical-mirrored",
+            Preset::Tiled => "tiled",
+        }
+    }
+}
+
+impl FromStr for Preset {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        Preset::ALL
+            .into_iter()
+            .find(|preset| preset.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<_> = Preset::ALL.iter().map(Preset::name).collect();
+                anyhow!(
+                    "unknown preset {:?}, expected one of {}",
+                    name,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+// The layout a window is opened with, either one of tmux's presets or a custom layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WindowLayout {
+    Preset(Preset),
+    Custom(Layout),
+}
+
+impl WindowLayout {
+    // Number of panes the layout describes, presets fit any number of panes
+    pub fn pane_count(&self) -> Option<usize> {
+        match self {
+            WindowLayout::Preset(_) => None,
+            WindowLayout::Custom(layout) => Some(layout.pane_count()),
+        }
+    }
+}
+
+impl FromStr for WindowLayout {
+    type Err = anyhow::Error;
+
+    fn from_str(layout: &str) -> Result<Self> {
+        // Layout strings always start with a checksum and a comma, anything
+        // else is meant to be a preset
+        if !layout.contains(',') {
+            return Ok(WindowLayout::Preset(layout.parse()?));
+        }
+        Ok(WindowLayout::Custom(layout.parse()?))
+    }
+}
+
+impl fmt::Display for WindowLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WindowLayout::Preset(preset) => write!(f, "{}", preset.name()),
+            WindowLayout::Custom(layout) => write!(f, "{}", layout),
+        }
+    }
+}
+
+// A layout written out as a tree in the config file, e.g.
+// `{ split = "horizontal", panes = [{ size = "60%", command = "nvim" }, {}] }`
+// which gets compiled into a tmux layout string
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct PaneTree {
+    pub split: Option<Direction>,
+    #[serde(default)]
+    pub panes: Vec<PaneTree>,
+    // percentage of the parent split, e.g. "60%"
+    pub size: Option<String>,
+    pub command: Option<String>,
+}
+
+// Size compiled pane trees are laid out at, before being scaled to the window they open in
+const TREE_WIDTH: u32 = 200;
+const TREE_HEIGHT: u32 = 50;
+
+// Same algorithm as `layout_checksum` in tmux's layout-custom.c
+pub fn checksum(body: &str) -> u16 {
+    body.bytes().fold(0u16, |csum, byte| {
+        ((csum >> 1) | ((csum & 1) << 15)).wrapping_add(byte as u16)
+    })
+}
+
+impl Cell {
+    pub fn pane_count(&self) -> usize {
+        match &self.kind {
+            CellKind::Pane(_) => 1,
+            CellKind::Split(_, children) => children.iter().map(Cell::pane_count).sum(),
+        }
+    }
+
+    // Moves and resizes this cell, sharing the new size between its
+    // children in the same proportions they had before
+    fn scale(&self, width: u32, height: u32, x: u32, y: u32) -> Result<Cell> {
+        if width == 0 || height == 0 {
+            return Err(anyhow!("there isn't enough room for every pane"));
+        }
+        let kind = match &self.kind {
+            CellKind::Pane(id) => CellKind::Pane(*id),
+            CellKind::Split(direction, children) => {
+                let size_along = |cell: &Cell| match direction {
+                    Direction::Horizontal => cell.width,
+                    Direction::Vertical => cell.height,
+                };
+                // children are separated by a one cell wide border
+                let borders = children.len() as u32 - 1;
+                let new_space = match direction {
+                    Direction::Horizontal => width,
+                    Direction::Vertical => height,
+                }
+                .checked_sub(borders)
+                .ok_or_else(|| anyhow!("there isn't enough room for every pane"))?;
+                let old_space = children.iter().map(|c| size_along(c) as u64).sum::<u64>();
+
+                let mut scaled = Vec::with_capacity(children.len());
+                let mut old_used = 0u64;
+                let mut start = 0u32;
+                for (i, child) in children.iter().enumerate() {
+                    old_used += size_along(child) as u64;
+                    let end =
+                        ((old_used * new_space as u64 + old_space / 2) / old_space.max(1)) as u32;
+                    let offset = start + i as u32;
+                    scaled.push(match direction {
+                        Direction::Horizontal => child.scale(end - start, height, x + offset, y)?,
+                        Direction::Vertical => child.scale(width, end - start, x, y + offset)?,
+                    });
+                    start = end;
+                }
+                CellKind::Split(*direction, scaled)
+            }
+        };
+        Ok(Cell {
+            width,
+            height,
+            x,
+            y,
+            kind,
+        })
+    }
+}
+
+impl Layout {
+    pub fn pane_count(&self) -> usize {
+        self.root.pane_count()
+    }
+
+    // Rescales the layout to a window of a different size,
+    // keeping the proportions of every split
+    pub fn scale(&self, width: u32, height: u32) -> Result<Layout> {
+        let root = self
+            .root
+            .scale(width, height, self.root.x, self.root.y)
+            .with_context(|| {
+                format!(
+                    "Couldn't fit a {}x{} layout into a {}x{} window",
+                    self.root.width, self.root.height, width, height
+                )
+            })?;
+        Ok(Layout { root })
+    }
+}
+
+impl PaneTree {
+    pub fn compile(&self) -> Result<Layout> {
+        Ok(Layout {
+            root: self.cell(TREE_WIDTH, TREE_HEIGHT, 0, 0)?,
+        })
+    }
+
+    // Commands of every pane, in the same order tmux numbers the panes
+    pub fn commands(&self) -> Vec<String> {
+        if self.panes.is_empty() {
+            vec![self.command.clone().unwrap_or_default()]
+        } else {
+            self.panes.iter().flat_map(PaneTree::commands).collect()
+        }
+    }
+
+    fn percentage(&self) -> Result<Option<u32>> {
+        match &self.size {
+            None => Ok(None),
+            Some(size) => size
+                .strip_suffix('%')
+                .and_then(|percent| percent.trim().parse().ok())
+                .filter(|percent| (1..=100).contains(percent))
+                .map(Some)
+                .ok_or_else(|| anyhow!("pane size {:?} should be a percentage like \"60%\"", size)),
+        }
+    }
+
+    fn cell(&self, width: u32, height: u32, x: u32, y: u32) -> Result<Cell> {
+        if self.panes.is_empty() {
+            if self.split.is_some() {
+                return Err(anyhow!("a split needs at least one pane in `panes`"));
+            }
+            return Ok(Cell {
+                width,
+                height,
+                x,
+                y,
+                kind: CellKind::Pane(None),
+            });
+        }
+        if self.command.is_some() {
+            return Err(anyhow!(
+                "a split can't have a command, put it on one of its panes"
+            ));
+        }
+        let direction = self
+            .split
+            .ok_or_else(|| anyhow!("panes need a `split` of \"horizontal\" or \"vertical\""))?;
+
+        let borders = self.panes.len() as u32 - 1;
+        let space = match direction {
+            Direction::Horizontal => width,
+            Direction::Vertical => height,
+        }
+        .saturating_sub(borders);
+
+        // panes with a size get their percentage, the rest share what's left equally
+        let percentages = self
+            .panes
+            .iter()
+            .map(PaneTree::percentage)
+            .collect::<Result<Vec<_>>>()?;
+        let sized: u32 = percentages.iter().flatten().sum();
+        let unsized_panes = percentages.iter().filter(|p| p.is_none()).count() as u32;
+        if sized > 100 || (sized == 100 && unsized_panes > 0) {
+            return Err(anyhow!("pane sizes in a split add up to more than 100%"));
+        }
+        let shares: Vec<u64> = percentages
+            .iter()
+            .map(|percent| match percent {
+                Some(percent) => *percent as u64 * unsized_panes.max(1) as u64,
+                None => (100 - sized) as u64,
+            })
+            .collect();
+        let total: u64 = shares.iter().sum();
+
+        let mut cells = Vec::with_capacity(self.panes.len());
+        let mut used = 0u64;
+        let mut start = 0u32;
+        for (i, (pane, share)) in self.panes.iter().zip(shares).enumerate() {
+            used += share;
+            let end = if i == self.panes.len() - 1 {
+                space
+            } else {
+                ((used * space as u64 + total / 2) / total) as u32
+            };
+            if end <= start {
+                return Err(anyhow!("a pane in the layout ends up with no room"));
+            }
+            let offset = start + i as u32;
+            cells.push(match direction {
+                Direction::Horizontal => pane.cell(end - start, height, x + offset, y)?,
+                Direction::Vertical => pane.cell(width, end - start, x, y + offset)?,
+            });
+            start = end;
+        }
+
+        Ok(Cell {
+            width,
+            height,
+            x,
+            y,
+            kind: CellKind::Split(direction, cells),
+        })
+    }
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{},{},{}", self.width, self.height, self.x, self.y)?;
+        match &self.kind {
+            CellKind::Pane(Some(id)) => write!(f, ",{}", id),
+            CellKind::Pane(None) => Ok(()),
+            CellKind::Split(direction, children) => {
+                let (open, close) = match direction {
+                    Direction::Horizontal => ('{', '}'),
+                    Direction::Vertical => ('[', ']'),
+                };
+                write!(f, "{}", open)?;
+                for (i, child) in children.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", child)?;
+                }
+                write!(f, "{}", close)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let body = self.root.to_string();
+        write!(f, "{:04x},{}", checksum(&body), body)
+    }
+}
+
+impl FromStr for Layout {
+    type Err = anyhow::Error;
+
+    fn from_str(layout: &str) -> Result<Self> {
+        let (sum, body) = layout
+            .split_once(',')
+            .ok_or_else(|| anyhow!("{:?} is missing a checksum", layout))?;
+        let expected = u16::from_str_radix(sum, 16)
+            .with_context(|| format!("{:?} isn't a valid layout checksum", sum))?;
+        if sum.len() != 4 {
+            return Err(anyhow!("{:?} isn't a valid layout checksum", sum));
+        }
+        let actual = checksum(body);
+        if expected != actual {
+            return Err(anyhow!(
+                "layout checksum {:04x} doesn't match its contents, expected {:04x}",
+                expected,
+                actual
+            ));
+        }
+
+        let mut parser = Parser {
+            input: body,
+            pos: 0,
+        };
+        let root = parser
+            .cell()
+            .with_context(|| format!("Couldn't parse layout {:?}", layout))?;
+        if parser.pos != body.len() {
+            return Err(anyhow!(
+                "Couldn't parse layout {:?}: unexpected {:?} at {}",
+                layout,
+                &body[parser.pos..],
+                parser.pos
+            ));
+        }
+        Ok(Layout { root })
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<()> {
+        match self.peek() {
+            Some(byte) if byte == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(byte) => Err(anyhow!(
+                "expected {:?} at {}, found {:?}",
+                expected as char,
+                self.pos,
+                byte as char
+            )),
+            None => Err(anyhow!(
+                "expected {:?} at {}, found end of layout",
+                expected as char,
+                self.pos
+            )),
+        }
+    }
+
+    fn number(&mut self) -> Result<u32> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(b'0'..=b'9')) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(anyhow!("expected a number at {}", start));
+        }
+        Ok(self.input[start..self.pos].parse()?)
+    }
+
+    // A pane id is only present when the digits after the comma aren't
+    // the width of the next cell
+    fn pane_id(&mut self) -> Result<Option<u32>> {
+        if self.peek() != Some(b',') {
+            return Ok(None);
+        }
+        let saved = self.pos;
+        self.pos += 1;
+        let id = self.number();
+        if id.is_err() || self.peek() == Some(b'x') {
+            self.pos = saved;
+            return Ok(None);
+        }
+        id.map(Some)
+    }
+
+    fn cell(&mut self) -> Result<Cell> {
+        let width = self.number()?;
+        self.expect(b'x')?;
+        let height = self.number()?;
+        self.expect(b',')?;
+        let x = self.number()?;
+        self.expect(b',')?;
+        let y = self.number()?;
+
+        let kind = match self.peek() {
+            Some(b'{') => CellKind::Split(Direction::Horizontal, self.children(b'}')?),
+            Some(b'[') => CellKind::Split(Direction::Vertical, self.children(b']')?),
+            _ => CellKind::Pane(self.pane_id()?),
+        };
+
+        Ok(Cell {
+            width,
+            height,
+            x,
+            y,
+            kind,
+        })
+    }
+
+    fn children(&mut self, close: u8) -> Result<Vec<Cell>> {
+        // skip the opening bracket
+        self.pos += 1;
+        let mut children = vec![self.cell()?];
+        while self.peek() == Some(b',') {
+            self.pos += 1;
+            children.push(self.cell()?);
+        }
+        self.expect(close)?;
+        Ok(children)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const README_LAYOUT: &str = "5e09,281x67,0,0{133x67,0,0,17,147x67,134,0[147x33,134,0,18,147x33,134,34{73x33,134,34,136,73x33,208,34[73x16,208,34,164,73x16,208,51,165]}]}";
+
+    #[test]
+    fn checksum_matches_tmux() {
+        assert_eq!(checksum("230x56,0,0{132x56,0,0,3,97x56,133,0,222}"), 0x34ed);
+    }
+
+    #[test]
+    fn parse_and_serialize_round_trip() {
+        for layout in [
+            README_LAYOUT,
+            "34ed,230x56,0,0{132x56,0,0,3,97x56,133,0,222}",
+        ] {
+            assert_eq!(layout.parse::<Layout>().unwrap().to_string(), layout);
+        }
+    }
+
+    #[test]
+    fn parse_builds_pane_tree() {
+        let layout: Layout = "34ed,230x56,0,0{132x56,0,0,3,97x56,133,0,222}"
+            .parse()
+            .unwrap();
+        match layout.root.kind {
+            CellKind::Split(Direction::Horizontal, children) => {
+                assert_eq!(children[1].width, 97);
+                assert_eq!(children[1].x, 133);
+                assert_eq!(children[1].kind, CellKind::Pane(Some(222)));
+            }
+            kind => panic!("expected a horizontal split, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn pane_count_counts_leaves() {
+        let layout: Layout = "34ed,230x56,0,0{132x56,0,0,3,97x56,133,0,222}"
+            .parse()
+            .unwrap();
+        assert_eq!(layout.pane_count(), 2);
+        assert_eq!(README_LAYOUT.parse::<Layout>().unwrap().pane_count(), 5);
+    }
+
+    #[test]
+    fn pane_ids_are_optional() {
+        let body = "80x24,0,0[80x12,0,0,80x11,0,13]";
+        let layout: Layout = format!("{:04x},{}", checksum(body), body).parse().unwrap();
+        assert_eq!(layout.root.to_string(), body);
+    }
+
+    #[test]
+    fn scale_to_same_size_changes_nothing() {
+        let layout: Layout = README_LAYOUT.parse().unwrap();
+        assert_eq!(layout.scale(281, 67).unwrap(), layout);
+    }
+
+    #[test]
+    fn scale_keeps_proportions() {
+        let layout: Layout = "34ed,230x56,0,0{132x56,0,0,3,97x56,133,0,222}"
+            .parse()
+            .unwrap();
+        let scaled = layout.scale(115, 28).unwrap();
+        assert_eq!(
+            scaled.root.to_string(),
+            "115x28,0,0{66x28,0,0,3,48x28,67,0,222}"
+        );
+        // the checksum is recomputed for the new layout
+        assert_eq!(
+            scaled.to_string().parse::<Layout>().unwrap().root,
+            scaled.root
+        );
+    }
+
+    #[test]
+    fn scale_fills_nested_splits_exactly() {
+        let layout: Layout = README_LAYOUT.parse().unwrap();
+        let scaled = layout.scale(100, 40).unwrap();
+        fn check(cell: &Cell) {
+            if let CellKind::Split(direction, children) = &cell.kind {
+                let (along, total) = match direction {
+                    Direction::Horizontal => {
+                        (children.iter().map(|c| c.width).sum::<u32>(), cell.width)
+                    }
+                    Direction::Vertical => {
+                        (children.iter().map(|c| c.height).sum::<u32>(), cell.height)
+                    }
+                };
+                assert_eq!(along + children.len() as u32 - 1, total);
+                children.iter().for_each(check);
+            }
+        }
+        check(&scaled.root);
+        assert_eq!(scaled.pane_count(), 5);
+    }
+
+    #[test]
+    fn scale_rejects_windows_that_are_too_small() {
+        let layout: Layout = README_LAYOUT.parse().unwrap();
+        assert!(layout.scale(3, 3).is_err());
+    }
+
+    fn pane(size: Option<&str>, command: Option<&str>) -> PaneTree {
+        PaneTree {
+            size: size.map(String::from),
+            command: command.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pane_tree_compiles_to_a_valid_layout() {
+        let tree = PaneTree {
+            split: Some(Direction::Horizontal),
+            panes: vec![
+                pane(Some("60%"), Some("nvim")),
+                PaneTree {
+                    split: Some(Direction::Vertical),
+                    panes: vec![pane(None, Some("fish")), pane(None, None)],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let layout = tree.compile().unwrap();
+        assert_eq!(
+            layout.root.to_string(),
+            "200x50,0,0{119x50,0,0,80x50,120,0[80x25,120,0,80x24,120,26]}"
+        );
+        assert_eq!(layout.to_string().parse::<Layout>().unwrap(), layout);
+        assert_eq!(tree.commands(), vec!["nvim", "fish", ""]);
+    }
+
+    #[test]
+    fn pane_tree_rejects_bad_sizes() {
+        let tree = |sizes: [Option<&str>; 2]| PaneTree {
+            split: Some(Direction::Vertical),
+            panes: sizes.iter().map(|size| pane(*size, None)).collect(),
+            ..Default::default()
+        };
+        assert!(tree([Some("60%"), Some("50%")]).compile().is_err());
+        assert!(tree([Some("100%"), None]).compile().is_err());
+        assert!(tree([Some("60"), None]).compile().is_err());
+        assert!(tree([Some("60%"), None]).compile().is_ok());
+    }
+
+    #[test]
+    fn pane_tree_needs_a_split_direction() {
+        let tree = PaneTree {
+            panes: vec![pane(None, None), pane(None, None)],
+            ..Default::default()
+        };
+        assert!(tree.compile().is_err());
+    }
+
+    #[test]
+    fn window_layout_accepts_presets_and_layout_strings() {
+        assert_eq!(
+            "main-vertical".parse::<WindowLayout>().unwrap(),
+            WindowLayout::Preset(Preset::MainVertical)
+        );
+        assert_eq!("tiled".parse::<WindowLayout>().unwrap().pane_count(), None);
+        let custom: WindowLayout = README_LAYOUT.parse().unwrap();
+        assert_eq!(custom.pane_count(), Some(5));
+        assert_eq!(custom.to_string(), README_LAYOUT);
+        assert!("main-diagonal".parse::<WindowLayout>().is_err());
+    }
+
+    #[test]
+    fn window_layout_reports_mistyped_presets() {
+        let err = "main-verticle".parse::<WindowLayout>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown preset \"main-verticle\", expected one of even-horizontal, even-vertical, \
+             main-horizontal, main-horizontal-mirrored, main-vertical, main-vertical-mirrored, tiled"
+        );
+    }
+
+    #[test]
+    fn parse_rejects_malformed_layouts() {
+        assert!("".parse::<Layout>().is_err());
+        assert!("nope".parse::<Layout>().is_err());
+        // wrong checksum
+        assert!("34ee,230x56,0,0{132x56,0,0,3,97x56,133,0,222}"
+            .parse::<Layout>()
+            .is_err());
+        // unbalanced brackets
+        let body = "230x56,0,0{132x56,0,0,3,97x56,133,0,222";
+        assert!(format!("{:04x},{}", checksum(body), body)
+            .parse::<Layout>()
+            .is_err());
+    }
+}
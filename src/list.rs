@@ -0,0 +1,245 @@
+// Lists the running workspaces dmux opened for `dmux list`
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+
+use crate::app::ListArgs;
+use crate::backend::{is_no_server, Backend};
+use crate::tmux::WindowInfo;
+
+#[derive(Serialize, Debug, PartialEq)]
+struct Session {
+    name: String,
+    attached: bool,
+    // when its first window was opened, in seconds since the epoch
+    created: Option<u64>,
+    windows: Vec<Window>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct Window {
+    index: u32,
+    name: String,
+    root: PathBuf,
+    profile: Option<String>,
+    panes: u32,
+    created: Option<u64>,
+}
+
+// Paths in the home directory are shown starting with `~`
+pub fn display_dir(dir: &Path) -> String {
+    match dirs::home_dir().and_then(|home| dir.strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(dir) if dir.as_os_str().is_empty() => "~".to_string(),
+        Some(dir) => format!("~/{}", dir.display()),
+        None => dir.display().to_string(),
+    }
+}
+
+// Every window on the server, none when there's no tmux server running
+pub fn server_windows(tmux: &mut dyn Backend) -> Result<Vec<WindowInfo>> {
+    match tmux.list_windows(None) {
+        Err(err) if is_no_server(&err) => Ok(vec![]),
+        windows => windows.context("Couldn't list the running windows"),
+    }
+}
+
+// The windows dmux opened
+pub fn running_windows(tmux: &mut dyn Backend) -> Result<Vec<WindowInfo>> {
+    Ok(server_windows(tmux)?
+        .into_iter()
+        .filter(|window| window.root.is_some())
+        .collect())
+}
+
+// A running window as the picker shows it
+pub fn picker_line(window: &WindowInfo) -> String {
+    format!(
+        "{}:{}  {}",
+        window.session,
+        window.name,
+        window.root.as_deref().map(display_dir).unwrap_or_default()
+    )
+}
+
+// How long ago something happened, roughly
+pub fn age(created: Option<u64>, now: u64) -> String {
+    let seconds = match created {
+        Some(created) => now.saturating_sub(created),
+        None => return "-".to_string(),
+    };
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+// The sessions with windows dmux opened, with only those windows
+fn sessions(windows: Vec<WindowInfo>) -> Vec<Session> {
+    let mut sessions: Vec<Session> = vec![];
+    for window in windows {
+        let root = match window.root {
+            Some(root) => root,
+            None => continue,
+        };
+        let index = match sessions.iter().position(|s| s.name == window.session) {
+            Some(index) => index,
+            None => {
+                sessions.push(Session {
+                    name: window.session,
+                    attached: window.attached,
+                    created: None,
+                    windows: vec![],
+                });
+                sessions.len() - 1
+            }
+        };
+        let session = &mut sessions[index];
+        session.created = match (session.created, window.created) {
+            (Some(created), Some(window)) => Some(created.min(window)),
+            (created, window) => created.or(window),
+        };
+        session.windows.push(Window {
+            index: window.index,
+            name: window.name,
+            root,
+            profile: window.profile,
+            panes: window.panes,
+            created: window.created,
+        });
+    }
+    sessions
+}
+
+fn table(sessions: &[Session], now: u64) -> String {
+    let mut rows = vec![[
+        "SESSION", "WINDOW", "PROFILE", "PANES", "OPENED", "ATTACHED", "ROOT",
+    ]
+    .map(String::from)];
+    for session in sessions {
+        for window in &session.windows {
+            rows.push([
+                session.name.clone(),
+                window.name.clone(),
+                window.profile.clone().unwrap_or_else(|| "-".to_string()),
+                window.panes.to_string(),
+                age(window.created, now),
+                if session.attached { "yes" } else { "no" }.to_string(),
+                display_dir(&window.root),
+            ]);
+        }
+    }
+    let widths: Vec<usize> = (0..7)
+        .map(|column| rows.iter().map(|row| row[column].len()).max().unwrap_or(0))
+        .collect();
+    rows.iter()
+        .map(|row| {
+            let line: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .collect();
+            line.join("  ").trim_end().to_string() + "\n"
+        })
+        .collect()
+}
+
+pub fn list(args: ListArgs, tmux: &mut dyn Backend) -> Result<()> {
+    let sessions = sessions(server_windows(tmux)?);
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&sessions)?);
+    } else if sessions.is_empty() {
+        eprintln!("There are no dmux workspaces running");
+    } else {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        print!("{}", table(&sessions, now));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::TmuxBackend;
+
+    fn window(session: &str, index: u32, name: &str, created: Option<u64>) -> WindowInfo {
+        WindowInfo {
+            session: session.to_owned(),
+            index,
+            name: name.to_owned(),
+            active: index == 0,
+            layout: String::new(),
+            panes: index + 1,
+            attached: session == "web",
+            root: created.map(|_| PathBuf::from("/srv/web")),
+            profile: created.map(|_| "js".to_owned()),
+            created,
+        }
+    }
+
+    fn windows() -> Vec<WindowInfo> {
+        vec![
+            window("web", 0, "editor", Some(1_000)),
+            window("web", 1, "zsh", None),
+            window("web", 2, "logs", Some(900)),
+            window("notes", 0, "zsh", None),
+        ]
+    }
+
+    #[test]
+    fn sessions_group_the_windows_dmux_opened() {
+        let sessions = sessions(windows());
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].created, Some(900));
+        assert!(sessions[0].attached);
+        let names: Vec<&str> = sessions[0]
+            .windows
+            .iter()
+            .map(|window| window.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["editor", "logs"]);
+    }
+
+    #[test]
+    fn age_rounds_down_to_the_largest_unit() {
+        assert_eq!(age(Some(1_000), 1_030), "just now");
+        assert_eq!(age(Some(1_000), 1_000 + 150), "2m ago");
+        assert_eq!(age(Some(1_000), 1_000 + 7_200), "2h ago");
+        assert_eq!(age(Some(1_000), 1_000 + 3 * 86_400), "3d ago");
+        assert_eq!(age(None, 1_000), "-");
+    }
+
+    #[test]
+    fn table_lines_up_its_columns() {
+        assert_eq!(
+            table(&sessions(windows()), 1_200),
+            "SESSION  WINDOW  PROFILE  PANES  OPENED  ATTACHED  ROOT\n\
+             web      editor  js       1      3m ago  yes       /srv/web\n\
+             web      logs    js       3      5m ago  yes       /srv/web\n"
+        );
+    }
+
+    #[test]
+    fn sessions_render_as_json() {
+        let json = serde_json::to_value(sessions(windows())).unwrap();
+        assert_eq!(json[0]["name"], "web");
+        assert_eq!(json[0]["windows"][1]["profile"], "js");
+        assert_eq!(json[0]["windows"][1]["created"], 900);
+    }
+
+    #[test]
+    fn no_server_means_no_windows() {
+        let mut tmux = TmuxBackend::new(Some("dmux-never-started".to_owned()), None);
+        if !tmux.is_installed() {
+            eprintln!("tmux isn't installed, skipping");
+            return;
+        }
+        assert!(server_windows(&mut tmux).unwrap().is_empty());
+    }
+}
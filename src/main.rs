@@ -4,16 +4,32 @@ extern crate serde_derive;
 extern crate anyhow;
 
 mod app;
+mod backend;
+mod config_file;
+mod describe;
+mod export;
+mod import;
+mod inherit;
+mod kill;
+mod layout;
+mod list;
+mod rules;
 mod select;
+mod snapshot;
+mod template;
 mod tmux;
+#[cfg(test)]
+mod tmux_server_tests;
+mod trust;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use app::CommandType;
+use backend::{Backend, Recorder, TmuxBackend};
 use colored::*;
-use select::Selector;
+use select::{Selection, Selector};
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
-use tmux::WorkSpace;
+use tmux::{WindowInfo, WorkSpace};
 use url::Url;
 
 fn main() {
@@ -27,51 +43,137 @@ fn main() {
 }
 
 fn run_command() -> Result<()> {
-    let command = app::build_app()?;
+    let (command, mut tmux) = app::build_app()?;
 
-    if !tmux::has_tmux() {
+    // an exported script is meant to be run elsewhere and importing and
+    // trusting only write files, so tmux isn't needed for any of them
+    if !matches!(
+        command,
+        CommandType::Export(_) | CommandType::Import(_) | CommandType::Trust(_)
+    ) && !tmux.is_installed()
+    {
         return Err(anyhow!("Tmux is not installed."));
     }
     match command {
         CommandType::Open(open_config) => open_selected_dir(open_config),
         CommandType::Select(select_config) => {
-            match Selector::new(&select_config.workspace.search_dir).select_dir()? {
-                Some(dir) => open_selected_dir(app::OpenArgs {
-                    selected_dir: dir,
-                    workspace: select_config.workspace,
-                }),
+            let running = if select_config.workspace.list_running {
+                list::running_windows(&mut tmux)?
+            } else {
+                vec![]
+            };
+            match Selector::new(&select_config.workspace.search_dir)
+                .with_running(running.iter().map(list::picker_line).collect())
+                .select()?
+            {
+                Some(Selection::Dir(dir)) => {
+                    open_selected_dir(select_config.command_line.open_args(dir)?)
+                }
+                Some(Selection::Running(index)) => switch_to_window(
+                    &running[index],
+                    select_config.workspace.dry_run,
+                    &mut tmux,
+                ),
                 None => Ok(()),
             }
         }
+        CommandType::Pull(pull_config) if pull_config.workspace.dry_run => Err(anyhow!(
+            "--dry-run can't be used with clone, clone the repo first and then open it with --dry-run"
+        )),
         CommandType::Pull(pull_config) => match clone_from(&pull_config) {
-            Ok(dir) => open_selected_dir(app::OpenArgs {
-                selected_dir: dir,
-                workspace: pull_config.workspace,
-            }),
+            Ok(dir) => open_selected_dir(pull_config.command_line.open_args(dir)?),
             Err(err) => Err(err),
         },
-        CommandType::Layout => {
-            if !tmux::in_tmux() {
-                return Err(anyhow!("Not inside a tmux session. Run `tmux a` and select the window you want the layout of."));
+        CommandType::Layout(layout_config) => {
+            if !tmux.in_tmux() && layout_config.session.is_none() && !layout_config.all {
+                return Err(anyhow!("Not inside a tmux session. Run `tmux a` and select the window you want the layout of, or pass --session."));
             };
-            tmux::generate_layout()
+            describe::print_layouts(layout_config, &mut tmux)
+        }
+        CommandType::Export(export_config) => {
+            let profile = export_config.workspace.profile_name().to_string();
+            // the profile the rules picked for the directory might name another server
+            let tmux = export_config.workspace.tmux_backend();
+            let workspace = workspace_for(export_config)?;
+            print!("{}", export::script(&workspace, &profile, &tmux)?);
+            Ok(())
+        }
+        CommandType::Import(import_config) => import::import(import_config),
+        CommandType::Trust(trust_config) => trust::trust(trust_config),
+        CommandType::Kill(kill_config) => kill::kill(kill_config, &mut tmux),
+        CommandType::List(list_config) => list::list(list_config, &mut tmux),
+        CommandType::Save(save_config) => {
+            if !tmux.in_tmux() && save_config.session.is_none() && !save_config.all {
+                return Err(anyhow!("Not inside a tmux session. Run `tmux a` and select the session you want to save, or name it."));
+            };
+            snapshot::save(save_config, &mut tmux)
+        }
+        CommandType::Restore(restore_config) if restore_config.dry_run => {
+            let mut recorder = Recorder::dry_run(&tmux);
+            snapshot::restore(restore_config, &mut recorder)?;
+            for line in recorder.script() {
+                println!("{}", line);
+            }
+            Ok(())
+        }
+        CommandType::Restore(restore_config) => snapshot::restore(restore_config, &mut tmux),
+        CommandType::Freeze(freeze_config) => {
+            if !tmux.in_tmux() && freeze_config.session.is_none() {
+                return Err(anyhow!("Not inside a tmux session. Run `tmux a` and select the window you want to freeze, or pass --session."));
+            };
+            describe::freeze(freeze_config, &mut tmux)
         }
     }
 }
 
-fn open_selected_dir(config: app::OpenArgs) -> Result<()> {
+fn workspace_for(config: app::OpenArgs) -> Result<WorkSpace> {
     if !config.selected_dir.exists() {
         return Err(anyhow!("{:?} isn't a valid path", config.selected_dir));
     }
-    tmux::setup_workspace(WorkSpace {
-        commands: config.workspace.commands,
+    let windows = config
+        .workspace
+        .to_windows()
+        .with_context(|| format!("Invalid profile {:?}", config.workspace.profile_name()))?;
+    let mut workspace = WorkSpace {
         path: config.selected_dir,
+        profile: config.workspace.profile_name().to_string(),
         session_name: config.workspace.session_name,
-        format_checksum: config.workspace.layout,
-        window_name: config.workspace.window_name,
-        number_of_panes: config.workspace.number_of_panes,
-    });
-    Ok(())
+        windows,
+        start_window: config.workspace.start_window,
+    };
+    template::expand_workspace(&mut workspace)?;
+    workspace.start_window_index()?;
+    Ok(workspace)
+}
+
+// Talks to the server named by the args for the directory, which can come
+// from a profile the rules picked or a dmux.local file
+fn open_selected_dir(config: app::OpenArgs) -> Result<()> {
+    let tmux = &mut config.workspace.tmux_backend();
+    let dry_run = config.workspace.dry_run;
+    let workspace = workspace_for(config)?;
+    if dry_run {
+        let mut recorder = Recorder::dry_run(tmux);
+        tmux::setup_workspace(workspace, &mut recorder)?;
+        for line in recorder.script() {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+    tmux::setup_workspace(workspace, tmux)
+}
+
+fn switch_to_window(window: &WindowInfo, dry_run: bool, tmux: &mut TmuxBackend) -> Result<()> {
+    let target = format!("{}:{}", window.session, window.index);
+    if dry_run {
+        let mut recorder = Recorder::dry_run(tmux);
+        tmux::attach_to_window(&target, &mut recorder)?;
+        for line in recorder.script() {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+    tmux::attach_to_window(&target, tmux)
 }
 
 fn git_url_to_dir_name(git_url: &str) -> Result<String> {
@@ -79,13 +181,13 @@ fn git_url_to_dir_name(git_url: &str) -> Result<String> {
         Ok(url
             .path_segments()
             .ok_or_else(|| anyhow!("cannot be base"))?
-            .last()
+            .next_back()
             .ok_or_else(|| anyhow!("no segments"))?
             .replace(".git", ""))
     } else {
         Ok(git_url
             .split('/')
-            .last()
+            .next_back()
             .ok_or_else(|| anyhow!("I don't know how to parse a dir from {:?}", git_url))?
             .replace(".git", ""))
     }
@@ -0,0 +1,209 @@
+// Picks the profile for a directory from the `[[rules]]` in the config file, e.g.
+// [[rules]]
+// marker = "Cargo.toml"
+// profile = "rust"
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use globset::GlobBuilder;
+
+use crate::config_file::expand_home;
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub profile: String,
+    // a glob the directory has to match, starting with `~` for the home directory
+    #[serde(default)]
+    pub dir: Option<String>,
+    // a file or directory the directory has to contain
+    #[serde(default)]
+    pub marker: Option<String>,
+}
+
+impl Rule {
+    // A rule with both a dir and a marker needs both to match
+    fn matches(&self, dir: &Path) -> Result<bool> {
+        if self.dir.is_none() && self.marker.is_none() {
+            return Err(anyhow!(
+                "The rule for profile {:?} needs a dir or a marker",
+                self.profile
+            ));
+        }
+        if let Some(glob) = &self.dir {
+            let glob = expand_home(glob);
+            let glob = GlobBuilder::new(&glob.to_string_lossy())
+                .literal_separator(true)
+                .build()
+                .with_context(|| format!("{:?} isn't a valid glob", glob))?;
+            if !glob.compile_matcher().is_match(dir) {
+                return Ok(false);
+            }
+        }
+        Ok(self
+            .marker
+            .as_ref()
+            .is_none_or(|marker| dir.join(marker).exists()))
+    }
+
+    fn describe(&self) -> String {
+        let conditions: Vec<String> = [("dir", &self.dir), ("marker", &self.marker)]
+            .iter()
+            .filter_map(|(key, value)| value.as_ref().map(|value| format!("{} = {:?}", key, value)))
+            .collect();
+        conditions.join(", ")
+    }
+}
+
+// The rules in the config file, none when it has none
+pub fn read_rules(settings: &config::Config) -> Result<Vec<Rule>> {
+    match settings.get::<Vec<Rule>>("rules") {
+        Ok(rules) => Ok(rules),
+        Err(config::ConfigError::NotFound(_)) => Ok(vec![]),
+        Err(err) => Err(err).context("Invalid [[rules]] in the config file"),
+    }
+}
+
+// Where the profile of a workspace came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProfileChoice {
+    // with --profile, which always wins
+    Given(String),
+    // the first rule that matched, numbered from 1
+    Rule(usize, Rule),
+    Default,
+}
+
+impl ProfileChoice {
+    pub fn profile(&self) -> Option<&str> {
+        match self {
+            ProfileChoice::Given(profile) => Some(profile),
+            ProfileChoice::Rule(_, rule) => Some(&rule.profile),
+            ProfileChoice::Default => None,
+        }
+    }
+
+    // For --explain
+    pub fn explanation(&self, dir: &Path) -> String {
+        match self {
+            ProfileChoice::Given(profile) => format!(
+                "Using profile {:?} from --profile, so the rules weren't checked",
+                profile
+            ),
+            ProfileChoice::Rule(number, rule) => format!(
+                "Using profile {:?} for {}, from rule {} ({})",
+                rule.profile,
+                dir.display(),
+                number,
+                rule.describe()
+            ),
+            ProfileChoice::Default => format!(
+                "No rule matched {}, using the default profile",
+                dir.display()
+            ),
+        }
+    }
+}
+
+pub fn choose_profile(given: Option<&str>, rules: &[Rule], dir: &Path) -> Result<ProfileChoice> {
+    if let Some(profile) = given {
+        return Ok(ProfileChoice::Given(profile.to_string()));
+    }
+    for (index, rule) in rules.iter().enumerate() {
+        if rule.matches(dir)? {
+            return Ok(ProfileChoice::Rule(index + 1, rule.clone()));
+        }
+    }
+    Ok(ProfileChoice::Default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn rule(profile: &str, dir: Option<&str>, marker: Option<&str>) -> Rule {
+        Rule {
+            profile: profile.to_owned(),
+            dir: dir.map(String::from),
+            marker: marker.map(String::from),
+        }
+    }
+
+    // a project with a package.json at <tmp>/dmux-rules-<pid>/work/infra/web
+    fn project() -> std::path::PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("dmux-rules-{}", std::process::id()))
+            .join("work/infra/web");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("package.json"), "{}").unwrap();
+        dir
+    }
+
+    #[test]
+    fn rules_match_on_dirs_and_markers() {
+        let dir = project();
+        let root = dir.parent().unwrap().parent().unwrap().parent().unwrap();
+        let infra = format!("{}/work/infra/**", root.display());
+        let rules = vec![
+            rule("rust", None, Some("Cargo.toml")),
+            rule("ops", Some(&infra), Some("main.tf")),
+            rule("javascript", None, Some("package.json")),
+            rule("ops", Some(&infra), None),
+        ];
+        assert_eq!(
+            choose_profile(None, &rules, &dir).unwrap(),
+            ProfileChoice::Rule(3, rules[2].clone())
+        );
+        assert_eq!(
+            choose_profile(None, &rules[3..], &dir).unwrap(),
+            ProfileChoice::Rule(1, rules[3].clone())
+        );
+        // `*` doesn't reach into subdirectories
+        let shallow = format!("{}/work/*", root.display());
+        assert!(!rule("ops", Some(&shallow), None).matches(&dir).unwrap());
+        assert_eq!(
+            choose_profile(None, &rules[..2], &dir).unwrap(),
+            ProfileChoice::Default
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn a_given_profile_wins() {
+        let rules = vec![rule("javascript", Some("/**"), None)];
+        let choice = choose_profile(Some("notes"), &rules, Path::new("/srv/web")).unwrap();
+        assert_eq!(choice.profile(), Some("notes"));
+    }
+
+    #[test]
+    fn rules_need_something_to_match() {
+        let rules = vec![rule("javascript", None, None)];
+        assert!(choose_profile(None, &rules, Path::new("/srv/web")).is_err());
+    }
+
+    #[test]
+    fn explanations_name_the_rule() {
+        let choice = ProfileChoice::Rule(2, rule("rust", Some("~/code/**"), Some("Cargo.toml")));
+        assert_eq!(
+            choice.explanation(Path::new("/srv/api")),
+            "Using profile \"rust\" for /srv/api, from rule 2 (dir = \"~/code/**\", marker = \"Cargo.toml\")"
+        );
+    }
+
+    #[test]
+    fn rules_are_read_from_the_config() {
+        let mut settings = config::Config::default();
+        settings
+            .merge(config::File::from_str(
+                "[[rules]]\nmarker = \"Cargo.toml\"\nprofile = \"rust\"\n",
+                config::FileFormat::Toml,
+            ))
+            .unwrap();
+        assert_eq!(
+            read_rules(&settings).unwrap(),
+            vec![rule("rust", None, Some("Cargo.toml"))]
+        );
+        assert!(read_rules(&config::Config::default()).unwrap().is_empty());
+    }
+}
@@ -1,12 +1,12 @@
 use anyhow::Result;
-use std::io::Write;
+use std::io::{self, Write};
 use std::path::PathBuf;
 use std::process::{Command, Output, Stdio};
 use walkdir::{DirEntry, WalkDir};
 
 fn is_git_dir(entry: &DirEntry) -> bool {
     if let Some(file_name) = entry.file_name().to_str() {
-        file_name.contains(&"git")
+        file_name.contains("git")
     } else {
         false
     }
@@ -18,27 +18,72 @@ fn all_dirs_in_path(search_dir: &PathBuf) -> String {
         .max_depth(4)
         .into_iter()
         .filter_entry(|e| e.file_type().is_dir() && !is_git_dir(e))
+        .flatten()
     {
-        if let Ok(path) = entry {
-            path_input.push_str("\n");
-            path_input.push_str(path.path().to_str().unwrap());
-        }
+        path_input.push_str(entry.path().to_str().unwrap());
+        path_input.push('\n');
     }
     path_input
 }
 
+// Lets the user pick any number of the lines, none when the picker is closed
+pub fn select_lines(lines: &[String], prompt: &str) -> Result<Vec<String>> {
+    let mut fzf = Command::new("fzf-tmux")
+        .args(["--multi", "--prompt", prompt])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    fzf.stdin
+        .as_mut()
+        .ok_or_else(|| anyhow!("fzf couldn't take stdin"))?
+        .write_all(lines.join("\n").as_bytes())?;
+    let output = fzf.wait_with_output()?;
+    if !output.status.success() {
+        return Ok(vec![]);
+    }
+    Ok(String::from_utf8(output.stdout)?
+        .lines()
+        .map(String::from)
+        .collect())
+}
+
 pub struct Selector {
     search_dir: PathBuf,
     use_fd: bool,
+    // lines for running windows, listed before the dirs
+    running: Vec<String>,
+}
+
+// What was picked, a dir or the index of one of the running windows
+#[derive(Debug, PartialEq, Eq)]
+pub enum Selection {
+    Dir(PathBuf),
+    Running(usize),
 }
 
-fn output_to_pathbuf(output: Output) -> Option<PathBuf> {
+// Running windows are marked so they stand out from the dirs after them
+const RUNNING_MARK: &str = "* ";
+
+fn running_input(running: &[String]) -> String {
+    running
+        .iter()
+        .map(|line| format!("{}{}\n", RUNNING_MARK, line))
+        .collect()
+}
+
+fn output_to_selection(output: Output, running: &[String]) -> Option<Selection> {
     if output.status.success() {
         let mut stdout = output.stdout;
         // removes trailing newline, probably a better way to do this
         stdout.pop();
-        let path: PathBuf = String::from_utf8(stdout).unwrap().parse().unwrap();
-        Some(path)
+        let line = String::from_utf8(stdout).unwrap();
+        let running = line
+            .strip_prefix(RUNNING_MARK)
+            .and_then(|line| running.iter().position(|running| running == line));
+        Some(match running {
+            Some(index) => Selection::Running(index),
+            None => Selection::Dir(line.parse().unwrap()),
+        })
     } else {
         // Err(anyhow!("Couldn't parse path from {:?}", output.stdout))
         None
@@ -55,10 +100,17 @@ impl Selector {
         Selector {
             search_dir: search_dir.to_owned(),
             use_fd,
+            running: vec![],
         }
     }
 
-    fn select_with_fd(&self) -> Result<Option<PathBuf>> {
+    // Lists these lines for running windows first
+    pub fn with_running(mut self, running: Vec<String>) -> Selector {
+        self.running = running;
+        self
+    }
+
+    fn select_with_fd(&self) -> Result<Option<Selection>> {
         let mut fd = Command::new("fd")
             .arg("-td")
             .arg(".")
@@ -70,21 +122,33 @@ impl Selector {
             .stdout(Stdio::piped())
             .spawn()?;
 
-        let pipe = fd
+        let mut pipe = fd
             .stdout
             .take()
             .ok_or_else(|| anyhow!("FD command's stdout could not be read"))?;
-        let fzf = Command::new("fzf-tmux")
-            .stdin(pipe)
+        let mut fzf = Command::new("fzf-tmux")
+            .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .spawn()?;
+        let mut stdin = fzf
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("fzf couldn't take stdin"))?;
+        let running = running_input(&self.running);
+        // fd's dirs are passed on as they're found, writing fails once fzf
+        // exits, which is fine since it's done reading
+        std::thread::spawn(move || {
+            let _ = stdin
+                .write_all(running.as_bytes())
+                .and_then(|_| io::copy(&mut pipe, &mut stdin));
+        });
         let output = fzf.wait_with_output()?;
         fd.kill()?;
-        Ok(output_to_pathbuf(output))
+        Ok(output_to_selection(output, &self.running))
     }
 
-    fn select_with_walk_dir(&self) -> Result<Option<PathBuf>> {
-        let files = all_dirs_in_path(&self.search_dir);
+    fn select_with_walk_dir(&self) -> Result<Option<Selection>> {
+        let files = running_input(&self.running) + &all_dirs_in_path(&self.search_dir);
         let mut fzf = Command::new("fzf-tmux")
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
@@ -99,10 +163,10 @@ impl Selector {
 
         let output = fzf.wait_with_output()?;
 
-        Ok(output_to_pathbuf(output))
+        Ok(output_to_selection(output, &self.running))
     }
 
-    pub fn select_dir(&self) -> Result<Option<PathBuf>> {
+    pub fn select(&self) -> Result<Option<Selection>> {
         if self.use_fd {
             self.select_with_fd()
         } else {
@@ -110,3 +174,33 @@ impl Selector {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+
+    fn output(code: i32, stdout: &str) -> Output {
+        Output {
+            status: std::process::ExitStatus::from_raw(code << 8),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: vec![],
+        }
+    }
+
+    #[test]
+    fn selections_tell_running_windows_from_dirs() {
+        let running = vec!["web:editor  ~/dev/web".to_owned()];
+        assert_eq!(running_input(&running), "* web:editor  ~/dev/web\n");
+        assert_eq!(
+            output_to_selection(output(0, "* web:editor  ~/dev/web\n"), &running),
+            Some(Selection::Running(0))
+        );
+        assert_eq!(
+            output_to_selection(output(0, "/home/me/dev/web\n"), &running),
+            Some(Selection::Dir(PathBuf::from("/home/me/dev/web")))
+        );
+        // closing the picker picks nothing
+        assert_eq!(output_to_selection(output(130, ""), &running), None);
+    }
+}
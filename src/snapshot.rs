@@ -0,0 +1,473 @@
+// Saves running sessions to snapshot files for `dmux save`
+// and opens them again for `dmux restore`
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use colored::*;
+
+use crate::app::{RestoreArgs, SaveArgs};
+use crate::backend::Backend;
+use crate::describe::{running_command, window_root};
+use crate::layout::{Layout, WindowLayout};
+use crate::list::{self, display_dir};
+use crate::select;
+use crate::tmux::{self, Pane, PaneInfo, Window, WindowInfo, WorkSpace};
+
+// A session as it was when it was saved
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub session: String,
+    // when it was saved, in seconds since the epoch
+    pub saved: u64,
+    pub windows: Vec<SnapshotWindow>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotWindow {
+    pub name: String,
+    pub root: PathBuf,
+    pub profile: Option<String>,
+    pub layout: String,
+    pub active: bool,
+    pub panes: Vec<SnapshotPane>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotPane {
+    pub dir: PathBuf,
+    // empty for a plain shell
+    pub command: String,
+}
+
+// {xdg_data|~/Library/Application Support}/dmux/snapshots
+pub fn snapshot_dir() -> Result<PathBuf> {
+    Ok(dirs::data_dir()
+        .ok_or_else(|| anyhow!("Data dir couldn't be read"))?
+        .join("dmux/snapshots"))
+}
+
+// Snapshots are named after their session, with `%` and `/` percent-encoded
+// so that every session gets a file of its own
+fn snapshot_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!(
+        "{}.json",
+        name.replace('%', "%25").replace('/', "%2F")
+    ))
+}
+
+fn snapshot_window(window: WindowInfo, mut panes: Vec<PaneInfo>) -> Result<SnapshotWindow> {
+    window
+        .layout
+        .parse::<Layout>()
+        .with_context(|| format!("tmux gave an unexpected layout for {}", window.name))?;
+    panes.sort_by_key(|pane| pane.index);
+    // windows dmux opened know their root, for the others it's worked out from the panes
+    let root = window
+        .root
+        .or_else(|| window_root(&panes))
+        .or_else(|| panes.first().map(|pane| pane.path.clone()))
+        .ok_or_else(|| anyhow!("Window {} has no panes", window.name))?;
+    Ok(SnapshotWindow {
+        name: window.name,
+        root,
+        profile: window.profile,
+        layout: window.layout,
+        active: window.active,
+        panes: panes
+            .iter()
+            .map(|pane| SnapshotPane {
+                dir: pane.path.clone(),
+                command: running_command(pane),
+            })
+            .collect(),
+    })
+}
+
+pub fn snapshot(session: &str, tmux: &mut dyn Backend) -> Result<Snapshot> {
+    let windows = tmux
+        .list_windows(Some(session))
+        .with_context(|| format!("Couldn't list the windows of session {:?}", session))?;
+    let windows = windows
+        .into_iter()
+        .map(|window| {
+            let panes = tmux
+                .list_panes(&window)
+                .with_context(|| format!("Couldn't list the panes of {}", window.name))?;
+            snapshot_window(window, panes)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Snapshot {
+        session: session.to_string(),
+        saved: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default(),
+        windows,
+    })
+}
+
+// The names of the windows, numbering every window after the first with a
+// name that's taken, since windows are told apart by name when they're restored
+fn unique_names(windows: &[SnapshotWindow]) -> Vec<String> {
+    let mut names: Vec<String> = vec![];
+    for window in windows {
+        let mut name = window.name.clone();
+        let mut number = 1;
+        while names.contains(&name) {
+            number += 1;
+            name = format!("{}-{}", window.name, number);
+        }
+        names.push(name);
+    }
+    names
+}
+
+impl SnapshotWindow {
+    fn to_window(&self, name: String) -> Result<Window> {
+        Ok(Window {
+            name: Some(name),
+            layout: self
+                .layout
+                .parse::<WindowLayout>()
+                .with_context(|| format!("Invalid layout for window {}", self.name))?,
+            panes: self
+                .panes
+                .iter()
+                .map(|pane| Pane {
+                    command: pane.command.clone(),
+                    dir: Some(pane.dir.clone()),
+                    ..Pane::default()
+                })
+                .collect(),
+            number_of_panes: self.panes.len() as u8,
+            main_pane_width: None,
+            main_pane_height: None,
+        })
+    }
+}
+
+impl Snapshot {
+    // The workspace that opens the session the way it was saved.
+    // Every pane gets its own dir, so windows with different roots stay apart
+    pub fn to_workspace(&self) -> Result<WorkSpace> {
+        let first = self
+            .windows
+            .first()
+            .ok_or_else(|| anyhow!("Snapshot {:?} has no windows", self.session))?;
+        let names = unique_names(&self.windows);
+        let windows = self
+            .windows
+            .iter()
+            .zip(&names)
+            .map(|(window, name)| window.to_window(name.clone()))
+            .collect::<Result<_>>()?;
+        Ok(WorkSpace {
+            path: first.root.clone(),
+            session_name: self.session.clone(),
+            profile: first
+                .profile
+                .clone()
+                .unwrap_or_else(|| "default".to_string()),
+            windows,
+            start_window: self
+                .windows
+                .iter()
+                .position(|window| window.active)
+                .map(|index| names[index].clone()),
+        })
+    }
+
+    fn line(&self, width: usize, now: u64) -> String {
+        format!(
+            "{:width$}  {:10}  saved {:9}  {}",
+            self.session,
+            format!("{} windows", self.windows.len()),
+            list::age(Some(self.saved), now),
+            self.windows
+                .first()
+                .map(|window| display_dir(&window.root))
+                .unwrap_or_default(),
+            width = width
+        )
+    }
+}
+
+fn write_snapshot(snapshot: &Snapshot, dir: &Path) -> Result<PathBuf> {
+    fs::create_dir_all(dir).with_context(|| format!("Couldn't create {}", dir.display()))?;
+    let path = snapshot_path(dir, &snapshot.session);
+    fs::write(&path, serde_json::to_string_pretty(snapshot)?)
+        .with_context(|| format!("Couldn't write {}", path.display()))?;
+    Ok(path)
+}
+
+fn read_snapshot(path: &Path) -> Result<Snapshot> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Couldn't read {}", path.display()))?;
+    serde_json::from_str(&contents).with_context(|| format!("{} isn't a snapshot", path.display()))
+}
+
+// Every saved snapshot, by name, skipping files that can't be read
+fn read_snapshots(dir: &Path) -> Result<Vec<Snapshot>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("Couldn't read {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .collect();
+    paths.sort();
+    Ok(paths
+        .iter()
+        .filter_map(|path| match read_snapshot(path) {
+            Ok(snapshot) => Some(snapshot),
+            Err(err) => {
+                eprintln!("{}: {:#}, skipping it", "Warning".yellow(), err);
+                None
+            }
+        })
+        .collect())
+}
+
+fn picked(snapshots: Vec<Snapshot>) -> Result<Vec<Snapshot>> {
+    let width = snapshots
+        .iter()
+        .map(|snapshot| snapshot.session.len())
+        .max()
+        .unwrap_or_default();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    let lines: Vec<String> = snapshots
+        .iter()
+        .map(|snapshot| snapshot.line(width, now))
+        .collect();
+    let picked = select::select_lines(&lines, "restore> ").context("Couldn't start fzf-tmux")?;
+    Ok(snapshots
+        .into_iter()
+        .zip(&lines)
+        .filter(|(_, line)| picked.contains(line))
+        .map(|(snapshot, _)| snapshot)
+        .collect())
+}
+
+// The sessions with any window dmux opened
+fn managed_sessions(tmux: &mut dyn Backend) -> Result<Vec<String>> {
+    let mut sessions: Vec<String> = vec![];
+    for window in list::running_windows(tmux)? {
+        if !sessions.contains(&window.session) {
+            sessions.push(window.session);
+        }
+    }
+    Ok(sessions)
+}
+
+pub fn save(args: SaveArgs, tmux: &mut dyn Backend) -> Result<()> {
+    let sessions = if args.all {
+        managed_sessions(tmux)?
+    } else {
+        match args.session {
+            Some(session) => vec![session],
+            None => vec![tmux.current_session()?],
+        }
+    };
+    if sessions.is_empty() {
+        return Err(anyhow!("There are no dmux workspaces running"));
+    }
+    let dir = snapshot_dir()?;
+    for session in sessions {
+        let path = write_snapshot(&snapshot(&session, tmux)?, &dir)?;
+        println!("Saved {} to {}", session, path.display());
+    }
+    Ok(())
+}
+
+// Opens the snapshot's session and switches to it, or with `all`
+// opens every saved session without switching to any
+pub fn restore(args: RestoreArgs, tmux: &mut dyn Backend) -> Result<()> {
+    let dir = snapshot_dir()?;
+    let snapshots = match (&args.name, args.all) {
+        (_, true) => read_snapshots(&dir)?,
+        (Some(name), false) => {
+            let path = snapshot_path(&dir, name);
+            if !path.exists() {
+                return Err(anyhow!(
+                    "There's no snapshot named {:?} in {}",
+                    name,
+                    dir.display()
+                ));
+            }
+            vec![read_snapshot(&path)?]
+        }
+        (None, false) => {
+            let snapshots = read_snapshots(&dir)?;
+            if snapshots.is_empty() {
+                return Err(anyhow!("There are no snapshots in {}", dir.display()));
+            }
+            picked(snapshots)?
+        }
+    };
+    if !args.all && snapshots.len() == 1 {
+        return tmux::setup_workspace(snapshots[0].to_workspace()?, tmux);
+    }
+    for snapshot in snapshots {
+        tmux::create_workspace(&snapshot.to_workspace()?, tmux)
+            .with_context(|| format!("Couldn't restore {}", snapshot.session))?;
+        eprintln!("Restored {}", snapshot.session);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Recorder;
+
+    const LAYOUT: &str = "34ed,230x56,0,0{132x56,0,0,3,97x56,133,0,222}";
+
+    fn window(name: &str, active: bool, root: Option<&str>) -> WindowInfo {
+        WindowInfo {
+            session: "web".to_owned(),
+            index: 0,
+            name: name.to_owned(),
+            active,
+            layout: LAYOUT.to_owned(),
+            panes: 2,
+            attached: false,
+            root: root.map(PathBuf::from),
+            profile: root.map(|_| "js".to_owned()),
+            created: None,
+        }
+    }
+
+    fn pane(index: u32, command: &str, start_command: &str, path: &str) -> PaneInfo {
+        PaneInfo {
+            id: index + 3,
+            index,
+            command: command.to_owned(),
+            start_command: start_command.to_owned(),
+            path: PathBuf::from(path),
+        }
+    }
+
+    fn snapshot() -> Snapshot {
+        let editor = snapshot_window(
+            window("editor", false, Some("/srv/web")),
+            vec![
+                pane(1, "bash", "", "/srv/web"),
+                pane(0, "nvim", "", "/srv/web/src"),
+            ],
+        )
+        .unwrap();
+        let logs = snapshot_window(
+            window("logs", true, None),
+            vec![
+                pane(0, "tail", "\"tail -f log\"", "/var/log/web"),
+                pane(1, "zsh", "", "/var/log/web/old"),
+            ],
+        )
+        .unwrap();
+        Snapshot {
+            session: "web".to_owned(),
+            saved: 1_000,
+            windows: vec![editor, logs],
+        }
+    }
+
+    #[test]
+    fn windows_keep_their_root_panes_and_commands() {
+        let snapshot = snapshot();
+        let editor = &snapshot.windows[0];
+        assert_eq!(editor.root, PathBuf::from("/srv/web"));
+        assert_eq!(editor.profile.as_deref(), Some("js"));
+        assert_eq!(
+            editor.panes,
+            vec![
+                SnapshotPane {
+                    dir: PathBuf::from("/srv/web/src"),
+                    command: "nvim".to_owned(),
+                },
+                SnapshotPane {
+                    dir: PathBuf::from("/srv/web"),
+                    command: String::new(),
+                },
+            ]
+        );
+        // without a tag the root is where every pane is
+        let logs = &snapshot.windows[1];
+        assert_eq!(logs.root, PathBuf::from("/var/log/web"));
+        assert_eq!(logs.panes[0].command, "tail -f log");
+        let mut bad = window("bad", false, None);
+        bad.layout = "not a layout".to_owned();
+        assert!(snapshot_window(bad, vec![pane(0, "zsh", "", "/")]).is_err());
+    }
+
+    #[test]
+    fn snapshots_become_workspaces() {
+        let workspace = snapshot().to_workspace().unwrap();
+        assert_eq!(workspace.path, PathBuf::from("/srv/web"));
+        assert_eq!(workspace.profile, "js");
+        assert_eq!(workspace.start_window.as_deref(), Some("logs"));
+        assert_eq!(workspace.windows[1].name.as_deref(), Some("logs"));
+        assert_eq!(workspace.windows[1].number_of_panes, 2);
+        assert_eq!(
+            workspace.windows[1].panes[1].dir,
+            Some(PathBuf::from("/var/log/web/old"))
+        );
+        assert_eq!(workspace.windows[0].layout, LAYOUT.parse().unwrap());
+    }
+
+    #[test]
+    fn windows_with_the_same_name_are_restored_apart() {
+        let mut snapshot = snapshot();
+        snapshot.windows[0].name = "zsh".to_owned();
+        snapshot.windows[1].name = "zsh".to_owned();
+        let workspace = snapshot.to_workspace().unwrap();
+        assert_eq!(workspace.windows[0].name.as_deref(), Some("zsh"));
+        assert_eq!(workspace.windows[1].name.as_deref(), Some("zsh-2"));
+        assert_eq!(workspace.start_window.as_deref(), Some("zsh-2"));
+    }
+
+    #[test]
+    fn restoring_opens_every_window_in_its_own_dirs() {
+        let mut recorder = Recorder::default();
+        tmux::setup_workspace(snapshot().to_workspace().unwrap(), &mut recorder).unwrap();
+        let commands = recorder.command_lines();
+        assert_eq!(
+            commands[0],
+            "new-session -d -s web -n editor -c /srv/web/src -x 230 -y 56"
+        );
+        assert!(commands.contains(&"new-window -d -t web: -n logs -c /var/log/web".to_owned()));
+        assert!(commands.contains(&"split-window -t web:logs -c /var/log/web/old".to_owned()));
+        assert!(commands.contains(&"send-keys -t web:logs.0 'tail -f log' Enter".to_owned()));
+        assert_eq!(commands.last().unwrap(), "attach-session -t web:logs");
+    }
+
+    #[test]
+    fn snapshots_are_written_and_read_back() {
+        let dir = std::env::temp_dir().join(format!("dmux-snapshots-{}", std::process::id()));
+        let mut snapshot = snapshot();
+        snapshot.session = "a/b".to_owned();
+        let path = write_snapshot(&snapshot, &dir).unwrap();
+        assert_eq!(path, dir.join("a%2Fb.json"));
+        assert_eq!(read_snapshots(&dir).unwrap(), vec![snapshot.clone()]);
+        // sessions whose names differ only in `/` don't overwrite each other
+        let mut other = snapshot.clone();
+        other.session = "a_b".to_owned();
+        write_snapshot(&other, &dir).unwrap();
+        assert_eq!(read_snapshots(&dir).unwrap().len(), 2);
+        assert_ne!(snapshot_path(&dir, "a%2Fb"), path);
+        // a file that isn't a snapshot doesn't hide the others
+        fs::write(dir.join("broken.json"), "{").unwrap();
+        assert_eq!(read_snapshots(&dir).unwrap().len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(read_snapshots(&dir).unwrap().is_empty());
+    }
+}
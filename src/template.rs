@@ -0,0 +1,305 @@
+// Expands variables like `{dir_name}` in the session name, window names and
+// commands of a workspace. A `{` only starts a variable when a name and `}`
+// follow it, so shell code like `${HOME}`, `{}` or `awk '{print $1}'` is left
+// alone, and `{{` stands for a `{` that shouldn't start one
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result};
+use url::Url;
+
+use crate::tmux::WorkSpace;
+
+const VARIABLES: &str =
+    "dir, dir_name, parent, git_branch, git_remote, git_remote_owner, profile and env.NAME";
+
+// The values of the variables for one workspace, asking git only when they're used
+pub struct Variables {
+    dir: PathBuf,
+    profile: String,
+    // looks up `{env.NAME}`, the process environment outside of tests
+    env: fn(&str) -> Option<String>,
+    cache: HashMap<String, String>,
+}
+
+fn path_str(path: &Path) -> Result<String> {
+    path.to_str()
+        .map(String::from)
+        .ok_or_else(|| anyhow!("{:?} isn't valid unicode", path))
+}
+
+// The owner in a remote like `git@github.com:owner/repo.git` or `https://host/owner/repo`
+fn remote_owner(remote: &str) -> Option<String> {
+    let path = match Url::parse(remote) {
+        Ok(url) => url.path().to_string(),
+        Err(_) => remote
+            .split_once(':')
+            .map_or(remote, |(_, path)| path)
+            .to_string(),
+    };
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    segments
+        .len()
+        .checked_sub(2)
+        .map(|owner| segments[owner].to_string())
+}
+
+impl Variables {
+    pub fn new(dir: &Path, profile: &str) -> Variables {
+        Variables {
+            dir: dir.to_path_buf(),
+            profile: profile.to_string(),
+            env: |var| std::env::var(var).ok(),
+            cache: HashMap::new(),
+        }
+    }
+
+    fn git(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.dir)
+            .args(args)
+            .output()
+            .context("Couldn't run git")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "{}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8(output.stdout)?.trim().to_string())
+    }
+
+    // The short commit instead when no branch is checked out
+    fn git_branch(&self) -> Result<String> {
+        match self.git(&["branch", "--show-current"])? {
+            branch if branch.is_empty() => self.git(&["rev-parse", "--short", "HEAD"]),
+            branch => Ok(branch),
+        }
+    }
+
+    // The url of origin, or of the first remote when there's no origin
+    fn git_remote(&self) -> Result<String> {
+        let remotes = self.git(&["remote"])?;
+        let remote = remotes
+            .lines()
+            .find(|remote| *remote == "origin")
+            .or_else(|| remotes.lines().next())
+            .ok_or_else(|| anyhow!("{} has no git remotes", self.dir.display()))?;
+        self.git(&["remote", "get-url", remote])
+    }
+
+    fn lookup(&self, name: &str) -> Result<String> {
+        match name {
+            "dir" => path_str(&self.dir),
+            "dir_name" => path_str(Path::new(
+                self.dir
+                    .file_name()
+                    .ok_or_else(|| anyhow!("{:?} has no name", self.dir))?,
+            )),
+            "parent" => path_str(
+                self.dir
+                    .parent()
+                    .ok_or_else(|| anyhow!("{:?} has no parent", self.dir))?,
+            ),
+            "profile" => Ok(self.profile.clone()),
+            "git_branch" => self.git_branch(),
+            "git_remote" => self.git_remote(),
+            "git_remote_owner" => {
+                let remote = self.git_remote()?;
+                remote_owner(&remote)
+                    .ok_or_else(|| anyhow!("Couldn't find the owner in the remote {:?}", remote))
+            }
+            _ => match name.strip_prefix("env.") {
+                Some(var) if !var.is_empty() => (self.env)(var)
+                    .ok_or_else(|| anyhow!("The environment variable {} isn't set", var)),
+                _ => Err(anyhow!(
+                    "Unknown variable {{{}}}, the variables are {} (write {{{{ for a {{ that isn't a variable)",
+                    name,
+                    VARIABLES
+                )),
+            },
+        }
+    }
+
+    fn value(&mut self, name: &str) -> Result<String> {
+        if let Some(value) = self.cache.get(name) {
+            return Ok(value.clone());
+        }
+        let value = self
+            .lookup(name)
+            .with_context(|| format!("Couldn't expand {{{}}}", name))?;
+        self.cache.insert(name.to_string(), value.clone());
+        Ok(value)
+    }
+
+    pub fn expand(&mut self, template: &str) -> Result<String> {
+        let mut expanded = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            expanded.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            if let Some(after) = after.strip_prefix('{') {
+                expanded.push('{');
+                rest = after;
+                continue;
+            }
+            let name_len = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(after.len());
+            let name = &after[..name_len];
+            let is_variable = name.starts_with(|c: char| c.is_ascii_alphabetic())
+                && after[name_len..].starts_with('}')
+                // `${NAME}` is for the shell
+                && !expanded.ends_with('$');
+            if is_variable {
+                expanded.push_str(&self.value(name)?);
+                rest = &after[name_len + 1..];
+            } else {
+                expanded.push('{');
+                rest = after;
+            }
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+}
+
+// Expands the variables in the workspace's session name, window names and commands
+pub fn expand_workspace(workspace: &mut WorkSpace) -> Result<()> {
+    let mut variables = Variables::new(&workspace.path, &workspace.profile);
+    workspace.session_name = variables
+        .expand(&workspace.session_name)
+        .with_context(|| format!("Invalid session_name {:?}", workspace.session_name))?;
+    if let Some(start_window) = &workspace.start_window {
+        workspace.start_window = Some(
+            variables
+                .expand(start_window)
+                .with_context(|| format!("Invalid start_window {:?}", start_window))?,
+        );
+    }
+    for window in &mut workspace.windows {
+        if let Some(name) = &window.name {
+            window.name = Some(
+                variables
+                    .expand(name)
+                    .with_context(|| format!("Invalid window name {:?}", name))?,
+            );
+        }
+        for pane in &mut window.panes {
+            pane.command = variables
+                .expand(&pane.command)
+                .with_context(|| format!("Invalid command {:?}", pane.command))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables() -> Variables {
+        Variables::new(Path::new("/srv/web/api"), "rust")
+    }
+
+    #[test]
+    fn expand_replaces_variables() {
+        let mut variables = variables();
+        assert_eq!(
+            variables
+                .expand("nvim {dir}/README.md && cd {parent} # {dir_name} {profile}")
+                .unwrap(),
+            "nvim /srv/web/api/README.md && cd /srv/web # api rust"
+        );
+        variables.env = |var| (var == "DMUX_TEMPLATE_TEST").then(|| "debug".to_owned());
+        assert_eq!(
+            variables
+                .expand("RUST_LOG={env.DMUX_TEMPLATE_TEST}")
+                .unwrap(),
+            "RUST_LOG=debug"
+        );
+        assert!(variables.expand("{env.DMUX_TEMPLATE_UNSET}").is_err());
+    }
+
+    #[test]
+    fn expand_leaves_shell_braces_alone() {
+        let mut variables = variables();
+        for command in [
+            "echo ${HOME}",
+            "find . -exec ls {} \\;",
+            "awk '{print $1}'",
+            "echo {1..5}",
+            "echo {a,b}",
+            "}{",
+            "{",
+        ] {
+            assert_eq!(variables.expand(command).unwrap(), command);
+        }
+        assert_eq!(variables.expand("{{dir}").unwrap(), "{dir}");
+        assert_eq!(variables.expand("{{{dir_name}").unwrap(), "{api");
+    }
+
+    #[test]
+    fn unknown_variables_are_errors() {
+        let err = variables().expand("nvim {dri}").unwrap_err();
+        assert_eq!(err.to_string(), "Couldn't expand {dri}");
+        assert!(format!("{:?}", err).contains("Unknown variable {dri}, the variables are dir,"));
+    }
+
+    #[test]
+    fn remote_owner_handles_ssh_and_https_remotes() {
+        assert_eq!(
+            remote_owner("git@github.com:zdcthomas/dmux.git").as_deref(),
+            Some("zdcthomas")
+        );
+        assert_eq!(
+            remote_owner("https://github.com/zdcthomas/dmux").as_deref(),
+            Some("zdcthomas")
+        );
+        assert_eq!(
+            remote_owner("ssh://git@gitlab.com/group/sub/repo.git/").as_deref(),
+            Some("sub")
+        );
+        assert_eq!(remote_owner("/srv/git/repo.git").as_deref(), Some("git"));
+        assert_eq!(remote_owner("repo"), None);
+    }
+
+    #[test]
+    fn git_variables_come_from_the_repo() {
+        let dir = std::env::temp_dir().join(format!("dmux-template-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .arg("-C")
+                .arg(&dir)
+                .args(args)
+                .output()
+                .map(|output| output.status.success())
+                .unwrap_or(false)
+        };
+        if !git(&["init", "-q", "-b", "trunk"]) {
+            eprintln!("git isn't installed, skipping");
+            return;
+        }
+        git(&[
+            "remote",
+            "add",
+            "upstream",
+            "git@github.com:zdcthomas/dmux.git",
+        ]);
+        let mut variables = Variables::new(&dir, "default");
+        assert_eq!(
+            variables
+                .expand("{git_branch} {git_remote_owner} {git_remote}")
+                .unwrap(),
+            "trunk zdcthomas git@github.com:zdcthomas/dmux.git"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(Variables::new(&dir, "default")
+            .expand("{git_branch}")
+            .is_err());
+    }
+}
@@ -1,155 +1,331 @@
 // setup_workspace
 // generate_layout
-// in_tmux
-// has_tmux
 
 use std::cmp::max;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use anyhow::Result;
-use tmux_interface::{TargetSession, TmuxCommand, Windows};
+use crate::app::LayoutPolicy;
+use crate::backend::Backend;
+use crate::layout::WindowLayout;
+use anyhow::{Context, Result};
 
-pub fn has_tmux() -> bool {
-    std::process::Command::new("tmux")
-        .arg("-V")
-        .output()
-        .unwrap()
-        .status
-        .success()
-}
+// Window options dmux tags the windows it opens with, holding the directory and
+// profile of their workspace and when they were opened, in seconds since the epoch
+pub const ROOT_OPTION: &str = "@dmux_root";
+pub const PROFILE_OPTION: &str = "@dmux_profile";
+pub const CREATED_OPTION: &str = "@dmux_created";
 
-pub fn in_tmux() -> bool {
-    std::env::var("TMUX").is_ok()
+pub fn setup_workspace(workspace: WorkSpace, tmux: &mut dyn Backend) -> Result<()> {
+    let start_window = create_workspace(&workspace, tmux)?;
+    attach_to_window(&workspace.target_session(start_window, None)?, tmux)
 }
 
-pub fn setup_workspace(workspace: WorkSpace) {
-    let tmux = TmuxCommand::new();
+// Creates whatever part of the workspace doesn't exist yet and selects
+// the start window, returning its index
+pub fn create_workspace(workspace: &WorkSpace, tmux: &mut dyn Backend) -> Result<usize> {
     let session_with_right_name_exists = tmux
-        .has_session()
-        .target_session(&workspace.session_name)
-        .output()
-        .unwrap()
-        .success();
-
-    if session_with_right_name_exists {
-        let target_session = TargetSession::Raw(&workspace.session_name);
-        let window_with_right_name_exists =
-            Windows::get(&target_session, tmux_interface::WINDOW_ALL)
-                .unwrap()
-                .into_iter()
-                .any(|w| w.name.as_ref().unwrap() == &workspace.window_name());
-
-        if window_with_right_name_exists {
-            attach_to_window(&workspace, &tmux);
-        } else {
-            // create window
-            tmux.new_window()
-                .window_name(workspace.window_name())
-                .start_directory(workspace.path_str())
-                // first command goes in defaut pane
-                .detached()
-                .output()
-                .unwrap();
-
-            // one already exists from when the window was created
-            setup_panes_with_commands(&workspace, &tmux);
-
-            attach_to_window(&workspace, &tmux);
+        .has_session(&workspace.session_name)
+        .with_context(|| format!("Couldn't look for session {:?}", workspace.session_name))?;
+
+    // Custom layouts are scaled to the size of the session's windows, worked out before
+    // anything is created so the whole build can go to tmux in one call
+    let custom_layout_size = workspace
+        .windows
+        .iter()
+        .find_map(|window| match &window.layout {
+            WindowLayout::Custom(layout) => Some((layout.root.width, layout.root.height)),
+            WindowLayout::Preset(_) => None,
+        });
+
+    let (existing_windows, size): (Vec<String>, _) = if session_with_right_name_exists {
+        let existing_windows = tmux
+            .list_windows(Some(&workspace.session_name))
+            .with_context(|| {
+                format!(
+                    "Couldn't list the windows of session {:?}",
+                    workspace.session_name
+                )
+            })?
+            .into_iter()
+            .map(|window| window.name)
+            .collect();
+        let size = match custom_layout_size {
+            Some(_) => Some(
+                tmux.window_size(&format!("{}:", clean_str(&workspace.session_name)))
+                    .with_context(|| {
+                        format!(
+                            "Couldn't get the window size of session {:?}",
+                            workspace.session_name
+                        )
+                    })?,
+            ),
+            None => None,
         };
+        (existing_windows, size)
     } else {
         // No existing tmux session
 
-        // Create a new session
-        tmux.new_session()
-            .session_name(&workspace.session_name)
-            .start_directory(&workspace.path_str())
-            .detached()
-            .window_name(workspace.window_name())
-            .output()
-            .unwrap();
-
-        setup_panes_with_commands(&workspace, &tmux);
+        // Create a new session, which comes with the first window. It's made the
+        // size of the first custom layout, and tmux resizes it for the client later
+        let window_name = workspace.window_name(0)?;
+        tmux.new_session(
+            &workspace.session_name,
+            &window_name,
+            &workspace.pane_dir(0, 0)?,
+            &workspace.windows[0].pane_env(0),
+            custom_layout_size,
+        )
+        .with_context(|| format!("Couldn't create session {:?}", workspace.session_name))?;
 
-        attach_to_window(&workspace, &tmux);
+        // one pane already exists from when the window was created
+        setup_panes_with_commands(workspace, 0, custom_layout_size, tmux)?;
+        (vec![window_name], custom_layout_size)
     };
+
+    for index in 0..workspace.windows.len() {
+        let window_name = workspace.window_name(index)?;
+        // windows that already exist are reused as they are
+        if existing_windows.contains(&window_name) {
+            continue;
+        }
+        // create window, which comes with the first pane
+        tmux.new_window(
+            &clean_str(&workspace.session_name),
+            &window_name,
+            &workspace.pane_dir(index, 0)?,
+            &workspace.windows[index].pane_env(0),
+        )
+        .with_context(|| {
+            format!(
+                "Couldn't create window {:?} in session {:?}",
+                window_name, workspace.session_name
+            )
+        })?;
+
+        setup_panes_with_commands(workspace, index, size, tmux)?;
+    }
+
+    let start_window = workspace.start_window_index()?;
+    let target = workspace.target_session(start_window, None)?;
+    tmux.select_window(&target)
+        .with_context(|| format!("Couldn't select window {}", target))?;
+    tmux.flush()
+        .with_context(|| format!("Couldn't set up session {}", workspace.session_name))?;
+    Ok(start_window)
 }
 
-fn setup_panes_with_commands(workspace: &WorkSpace, tmux: &TmuxCommand) {
-    for _ in 0..workspace.number_of_panes() - 1 {
-        tmux.split_window()
-            .start_directory(workspace.path_str())
-            .target_pane(workspace.target_session(None))
-            .output()
-            .unwrap();
+// `size` is the width and height of the window, when it's known
+fn setup_panes_with_commands(
+    workspace: &WorkSpace,
+    index: usize,
+    size: Option<(u32, u32)>,
+    tmux: &mut dyn Backend,
+) -> Result<()> {
+    let window = &workspace.windows[index];
+    let window_target = workspace.target_session(index, None)?;
+    for pane in 1..window.number_of_panes() as usize {
+        tmux.split_window(
+            &window_target,
+            &workspace.pane_dir(index, pane)?,
+            &window.pane_env(pane),
+        )
+        .with_context(|| format!("Couldn't create pane {} of window {}", pane, window_target))?;
     }
 
-    tmux.select_layout()
-        .target_pane(workspace.target_session(Some(0)))
-        .layout_name(&workspace.format_checksum)
-        .output()
-        .unwrap();
+    // tagging the window lets dmux tell it apart from windows made by hand
+    let created = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+        .to_string();
+    for (option, value) in [
+        (ROOT_OPTION, workspace.root()?),
+        (PROFILE_OPTION, workspace.profile.clone()),
+        (CREATED_OPTION, created),
+    ] {
+        tmux.set_window_option(&window_target, option, &value)
+            .with_context(|| format!("Couldn't tag window {}", window_target))?;
+    }
+    for (option, value) in window.window_options() {
+        tmux.set_window_option(&window_target, option, value)
+            .with_context(|| format!("Couldn't set {} for window {}", option, window_target))?;
+    }
+
+    let layout = match &window.layout {
+        WindowLayout::Preset(preset) => preset.name().to_string(),
+        // a layout saved on a different screen size is scaled to fit this window,
+        // otherwise tmux would resize the window to the size of the layout
+        WindowLayout::Custom(layout) => size
+            .and_then(|(width, height)| layout.scale(width, height).ok())
+            .unwrap_or_else(|| layout.clone())
+            .to_string(),
+    };
+
+    tmux.select_layout(&workspace.target_session(index, Some(0))?, &layout)
+        .with_context(|| {
+            format!(
+                "Couldn't apply layout {:?} to window {}",
+                layout, window_target
+            )
+        })?;
+
+    // panes without a command are left as a plain shell
+    for (i, pane) in window.panes.iter().enumerate() {
+        let target = workspace.target_session(index, Some(i as u8))?;
+        if !pane.command.is_empty() {
+            tmux.send_keys(&target, &[&pane.command, "Enter"])
+                .with_context(|| format!("Couldn't run {:?} in pane {}", pane.command, target))?;
+        }
+        if let Some(title) = &pane.title {
+            tmux.select_pane(&target, Some(title))
+                .with_context(|| format!("Couldn't set the title of pane {}", target))?;
+        }
+    }
 
-    workspace.commands.iter().enumerate().for_each(|(i, com)| {
-        tmux.send_keys()
-            .target_pane(workspace.target_session(Some(i as u8)))
-            .key(format!("{}\r", com))
-            .output()
-            .unwrap();
-    });
+    // the last pane asking for focus wins, zooming a pane also focuses it
+    if let Some(i) = window
+        .panes
+        .iter()
+        .rposition(|pane| pane.focus || pane.zoom)
+    {
+        let target = workspace.target_session(index, Some(i as u8))?;
+        tmux.select_pane(&target, None)
+            .with_context(|| format!("Couldn't focus pane {}", target))?;
+        if window.panes[i].zoom {
+            tmux.zoom_pane(&target)
+                .with_context(|| format!("Couldn't zoom pane {}", target))?;
+        }
+    }
+    Ok(())
 }
 
-fn attach_to_window(workspace: &WorkSpace, tmux: &TmuxCommand) {
-    if in_tmux() {
+// Switches the client to the window when dmux runs inside tmux, otherwise attaches to it
+pub fn attach_to_window(target: &str, tmux: &mut dyn Backend) -> Result<()> {
+    if tmux.in_tmux() {
         // switch to the window which exists
-        tmux.switch_client()
-            .target_session(workspace.target_session(None))
-            .output()
-            .unwrap();
+        tmux.switch_client(target)
+            .with_context(|| format!("Couldn't switch to window {}", target))
     } else {
         // attach to the window in the session
-        tmux.attach_session()
-            .target_session(workspace.target_session(None))
-            .output()
-            .unwrap();
-    };
+        tmux.attach_session(target)
+            .with_context(|| format!("Couldn't attach to window {}", target))
+    }
 }
 
-pub fn generate_layout() -> Result<()> {
-    let tmux = TmuxCommand::new();
-
-    let stdout = tmux
-        .list_windows()
-        .format("#{window_active} #{window_layout}")
-        .output()?
-        .0
-        .stdout;
+// A window as reported by `tmux list-windows`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowInfo {
+    pub session: String,
+    pub index: u32,
+    pub name: String,
+    pub active: bool,
+    pub layout: String,
+    pub panes: u32,
+    // whether a client is attached to the window's session
+    pub attached: bool,
+    // the tags of windows dmux opened
+    pub root: Option<PathBuf>,
+    pub profile: Option<String>,
+    pub created: Option<u64>,
+}
 
-    let layout = match std::str::from_utf8(&stdout)?
-        .split('\n')
-        .find(|l| l.starts_with('1'))
-    {
-        Some(layout) => Ok(layout),
-        None => Err(anyhow!("Uh-oh, looks like you're not in a tmux session!")),
-    }?;
-
-    println!(
-        "{}",
-        layout
-            .split_whitespace()
-            .last()
-            .ok_or_else(|| anyhow!("layout invalid"))?
-    );
-    Ok(())
+// A pane as reported by `tmux list-panes`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaneInfo {
+    // the number after the `%` of the pane id, which is also the id in layout strings
+    pub id: u32,
+    pub index: u32,
+    pub command: String,
+    // only set when the pane was created with a command instead of a shell
+    pub start_command: String,
+    pub path: PathBuf,
 }
 
 #[derive(Debug, Clone)]
 pub struct WorkSpace {
     pub path: PathBuf,
     pub session_name: String,
-    pub format_checksum: String,
-    pub commands: Vec<String>,
-    pub window_name: Option<String>,
+    // name of the profile the workspace was made from
+    pub profile: String,
+    pub windows: Vec<Window>,
+    // name of the window to end up in, defaults to the first one
+    pub start_window: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Window {
+    pub name: Option<String>,
+    pub layout: WindowLayout,
+    pub panes: Vec<Pane>,
     pub number_of_panes: u8,
+    pub main_pane_width: Option<String>,
+    pub main_pane_height: Option<String>,
+}
+
+// What to run in a pane and how to start it. In the config a pane is
+// either just its command or a table with any of these fields
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(from = "PaneArg")]
+pub struct Pane {
+    pub command: String,
+    // relative to the workspace root
+    pub dir: Option<PathBuf>,
+    pub env: BTreeMap<String, String>,
+    pub title: Option<String>,
+    pub focus: bool,
+    pub zoom: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PaneArg {
+    Command(String),
+    Table {
+        #[serde(default)]
+        command: String,
+        dir: Option<PathBuf>,
+        #[serde(default)]
+        env: BTreeMap<String, String>,
+        title: Option<String>,
+        #[serde(default)]
+        focus: bool,
+        #[serde(default)]
+        zoom: bool,
+    },
+}
+
+impl From<PaneArg> for Pane {
+    fn from(arg: PaneArg) -> Pane {
+        match arg {
+            PaneArg::Command(command) => Pane::from(command),
+            PaneArg::Table {
+                command,
+                dir,
+                env,
+                title,
+                focus,
+                zoom,
+            } => Pane {
+                command,
+                dir,
+                env,
+                title,
+                focus,
+                zoom,
+            },
+        }
+    }
+}
+
+impl From<String> for Pane {
+    fn from(command: String) -> Pane {
+        Pane {
+            command,
+            ..Pane::default()
+        }
+    }
 }
 
 fn clean_str(string: &str) -> String {
@@ -157,63 +333,165 @@ fn clean_str(string: &str) -> String {
 }
 
 impl WorkSpace {
-    fn target_session(&self, pane: Option<u8>) -> String {
-        if let Some(pane) = pane {
+    pub fn target_session(&self, index: usize, pane: Option<u8>) -> Result<String> {
+        Ok(if let Some(pane) = pane {
             format!(
                 "{}:{}.{}",
                 clean_str(&self.session_name),
-                self.window_name(),
+                self.window_name(index)?,
                 pane
             )
         } else {
-            format!("{}:{}", clean_str(&self.session_name), self.window_name())
-        }
+            format!(
+                "{}:{}",
+                clean_str(&self.session_name),
+                self.window_name(index)?
+            )
+        })
     }
 
-    fn window_name(&self) -> String {
-        if let Some(name) = &self.window_name {
-            name.to_owned()
+    // Windows without a name are named after the directory,
+    // with their position appended for every window after the first
+    fn window_name(&self, index: usize) -> Result<String> {
+        if let Some(name) = &self.windows[index].name {
+            return Ok(name.to_owned());
+        }
+        let dir_name = self
+            .path
+            .file_name()
+            .ok_or_else(|| anyhow!("Couldn't name a window after {:?}", self.path))?
+            .to_str()
+            .ok_or_else(|| anyhow!("{:?} isn't valid unicode", self.path))?;
+        let dir_name = clean_str(dir_name);
+        Ok(if index == 0 {
+            dir_name
         } else {
-            clean_str(
-                &self
-                    .path
-                    .file_name()
-                    .unwrap()
-                    .to_owned()
-                    .into_string()
-                    .unwrap(),
-            )
+            format!("{}-{}", dir_name, index)
+        })
+    }
+
+    pub fn start_window_index(&self) -> Result<usize> {
+        match &self.start_window {
+            None => Ok(0),
+            Some(name) => {
+                for index in 0..self.windows.len() {
+                    if &self.window_name(index)? == name {
+                        return Ok(index);
+                    }
+                }
+                Err(anyhow!("start_window {:?} isn't one of the windows", name))
+            }
         }
     }
 
-    fn path_str(&self) -> String {
-        self.path.as_os_str().to_owned().into_string().unwrap()
+    fn root(&self) -> Result<String> {
+        self.path
+            .to_str()
+            .map(String::from)
+            .ok_or_else(|| anyhow!("{:?} isn't valid unicode", self.path))
     }
 
+    // The directory a pane starts in, panes without a dir start in the workspace root
+    fn pane_dir(&self, index: usize, pane: usize) -> Result<String> {
+        let dir = match self.windows[index]
+            .panes
+            .get(pane)
+            .and_then(|p| p.dir.as_ref())
+        {
+            Some(dir) => self.path.join(dir),
+            None => self.path.clone(),
+        };
+        dir.into_os_string()
+            .into_string()
+            .map_err(|dir| anyhow!("{:?} isn't valid unicode", dir))
+    }
+}
+
+impl Window {
     fn number_of_panes(&self) -> u8 {
-        max(self.commands.len() as u8, self.number_of_panes)
+        max(self.panes.len() as u8, self.number_of_panes)
+    }
+
+    // `KEY=value` pairs for the pane's environment
+    fn pane_env(&self, pane: usize) -> Vec<String> {
+        self.panes
+            .get(pane)
+            .map(|pane| {
+                pane.env
+                    .iter()
+                    .map(|(key, value)| format!("{}={}", key, value))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn window_options(&self) -> Vec<(&str, &str)> {
+        let mut options = vec![];
+        if let Some(width) = &self.main_pane_width {
+            options.push(("main-pane-width", width.as_str()));
+        }
+        if let Some(height) = &self.main_pane_height {
+            options.push(("main-pane-height", height.as_str()));
+        }
+        options
+    }
+
+    pub fn apply_layout_policy(&mut self, policy: LayoutPolicy) -> Result<()> {
+        let layout_panes = match self.layout.pane_count() {
+            Some(layout_panes) => layout_panes,
+            None => return Ok(()),
+        };
+        let panes = self.number_of_panes() as usize;
+        if layout_panes == panes || policy == LayoutPolicy::Ignore {
+            return Ok(());
+        }
+        if policy == LayoutPolicy::GrowPanes && layout_panes > panes {
+            self.number_of_panes = u8::try_from(layout_panes)
+                .map_err(|_| anyhow!("layout has too many panes ({})", layout_panes))?;
+            return Ok(());
+        }
+        Err(anyhow!(
+            "layout has {} panes, but the window has {} ({} commands, number_of_panes = {})",
+            layout_panes,
+            panes,
+            self.panes.len(),
+            self.number_of_panes
+        ))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backend::Recorder;
     #[test]
     fn clean_str_removes_dots_n_stuff() {
         assert_eq!(clean_str("foo.bar"), "foo-bar")
     }
 
+    fn window(name: Option<&str>) -> Window {
+        Window {
+            name: name.map(String::from),
+            layout: "34ed,230x56,0,0{132x56,0,0,3,97x56,133,0,222}"
+                .parse()
+                .unwrap(),
+            panes: vec![Pane::from("nvim".to_owned()), Pane::from("fish".to_owned())],
+            number_of_panes: 3,
+            main_pane_width: None,
+            main_pane_height: None,
+        }
+    }
+
     #[test]
     fn workplace_window_name_replaces_dots_n_spaces() {
         let wp = WorkSpace {
             path: PathBuf::from("/Users/zacharythomas/dev/foo.bar/"),
             session_name: "dev".to_owned(),
-            format_checksum: "34ed,230x56,0,0{132x56,0,0,3,97x56,133,0,222}".to_owned(),
-            commands: vec!["nvim".to_owned(), "fish".to_owned()],
-            window_name: None,
-            number_of_panes: 3,
+            profile: "default".to_owned(),
+            windows: vec![window(None)],
+            start_window: None,
         };
-        assert_eq!(wp.window_name(), "foo-bar")
+        assert_eq!(wp.window_name(0).unwrap(), "foo-bar")
     }
 
     #[test]
@@ -221,11 +499,271 @@ mod tests {
         let wp = WorkSpace {
             path: PathBuf::from("/Users/zacharythomas/dev/some_name/"),
             session_name: "dev".to_owned(),
-            format_checksum: "34ed,230x56,0,0{132x56,0,0,3,97x56,133,0,222}".to_owned(),
-            commands: vec!["nvim".to_owned(), "fish".to_owned()],
-            window_name: None,
-            number_of_panes: 3,
+            profile: "default".to_owned(),
+            windows: vec![window(None)],
+            start_window: None,
+        };
+        assert_eq!(wp.window_name(0).unwrap(), "some_name")
+    }
+
+    #[test]
+    fn workplace_window_name_numbers_unnamed_windows() {
+        let wp = WorkSpace {
+            path: PathBuf::from("/Users/zacharythomas/dev/some_name/"),
+            session_name: "dev".to_owned(),
+            profile: "default".to_owned(),
+            windows: vec![window(None), window(Some("servers")), window(None)],
+            start_window: Some("servers".to_owned()),
+        };
+        assert_eq!(wp.window_name(1).unwrap(), "servers");
+        assert_eq!(wp.window_name(2).unwrap(), "some_name-2");
+        assert_eq!(wp.target_session(1, Some(2)).unwrap(), "dev:servers.2");
+        assert_eq!(wp.start_window_index().unwrap(), 1);
+    }
+
+    #[test]
+    fn start_window_must_be_one_of_the_windows() {
+        let wp = WorkSpace {
+            path: PathBuf::from("/Users/zacharythomas/dev/some_name/"),
+            session_name: "dev".to_owned(),
+            profile: "default".to_owned(),
+            windows: vec![window(None)],
+            start_window: Some("logs".to_owned()),
+        };
+        assert!(wp.start_window_index().is_err());
+    }
+
+    fn five_pane_window() -> Window {
+        Window {
+            name: None,
+            layout: "5e09,281x67,0,0{133x67,0,0,17,147x67,134,0[147x33,134,0,18,147x33,134,34{73x33,134,34,136,73x33,208,34[73x16,208,34,164,73x16,208,51,165]}]}"
+                .parse()
+                .unwrap(),
+            panes: vec![Pane::from("nvim".to_owned()), Pane::from("fish".to_owned())],
+            number_of_panes: 2,
+            main_pane_width: None,
+            main_pane_height: None,
+        }
+    }
+
+    #[test]
+    fn layout_policy_strict_rejects_mismatched_pane_count() {
+        let mut wp = five_pane_window();
+        assert!(wp.apply_layout_policy(LayoutPolicy::Strict).is_err());
+        wp.number_of_panes = 5;
+        assert!(wp.apply_layout_policy(LayoutPolicy::Strict).is_ok());
+    }
+
+    #[test]
+    fn layout_policy_grow_panes_adds_panes() {
+        let mut wp = five_pane_window();
+        wp.apply_layout_policy(LayoutPolicy::GrowPanes).unwrap();
+        assert_eq!(wp.number_of_panes(), 5);
+
+        // there's no way to fit 6 commands into 5 panes
+        wp.panes = vec![Pane::from("ls".to_owned()); 6];
+        assert!(wp.apply_layout_policy(LayoutPolicy::GrowPanes).is_err());
+    }
+
+    #[test]
+    fn layout_policy_skips_presets() {
+        let mut wp = five_pane_window();
+        wp.layout = "main-vertical".parse().unwrap();
+        assert!(wp.apply_layout_policy(LayoutPolicy::Strict).is_ok());
+    }
+
+    #[test]
+    fn window_options_only_include_set_values() {
+        let mut wp = five_pane_window();
+        assert!(wp.window_options().is_empty());
+        wp.main_pane_width = Some("60%".to_owned());
+        assert_eq!(wp.window_options(), vec![("main-pane-width", "60%")]);
+    }
+
+    #[test]
+    fn layout_policy_ignore_leaves_panes_alone() {
+        let mut wp = five_pane_window();
+        wp.apply_layout_policy(LayoutPolicy::Ignore).unwrap();
+        assert_eq!(wp.number_of_panes(), 2);
+    }
+
+    #[derive(Deserialize)]
+    struct Panes {
+        commands: Vec<Pane>,
+    }
+
+    #[test]
+    fn panes_are_read_from_strings_or_tables() {
+        let panes: Panes = toml::from_str(
+            r#"
+            commands = [
+                "nvim",
+                { command = "cargo watch", dir = "server", env = { RUST_LOG = "debug" }, title = "server", focus = true },
+                { zoom = true },
+            ]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(panes.commands[0], Pane::from("nvim".to_owned()));
+        assert_eq!(panes.commands[1].command, "cargo watch");
+        assert_eq!(panes.commands[1].dir, Some(PathBuf::from("server")));
+        assert_eq!(panes.commands[1].title.as_deref(), Some("server"));
+        assert!(panes.commands[1].focus);
+        assert_eq!(panes.commands[2].command, "");
+        assert!(panes.commands[2].zoom);
+    }
+
+    #[test]
+    fn pane_dir_and_env_come_from_the_pane() {
+        let mut window = five_pane_window();
+        window.panes[1].dir = Some(PathBuf::from("server"));
+        window.panes[1]
+            .env
+            .insert("RUST_LOG".to_owned(), "debug".to_owned());
+        let wp = WorkSpace {
+            path: PathBuf::from("/home/me/dev/app"),
+            session_name: "dev".to_owned(),
+            profile: "default".to_owned(),
+            windows: vec![window],
+            start_window: None,
+        };
+        assert_eq!(wp.pane_dir(0, 0).unwrap(), "/home/me/dev/app");
+        assert_eq!(wp.pane_dir(0, 1).unwrap(), "/home/me/dev/app/server");
+        assert_eq!(wp.pane_dir(0, 4).unwrap(), "/home/me/dev/app");
+        assert!(wp.windows[0].pane_env(0).is_empty());
+        assert_eq!(wp.windows[0].pane_env(1), vec!["RUST_LOG=debug"]);
+    }
+
+    #[test]
+    fn window_name_fails_for_paths_without_a_name() {
+        let wp = WorkSpace {
+            path: PathBuf::from("/"),
+            session_name: "dev".to_owned(),
+            profile: "default".to_owned(),
+            windows: vec![window(None)],
+            start_window: None,
+        };
+        assert!(wp.window_name(0).is_err());
+        assert!(wp.target_session(0, Some(1)).is_err());
+    }
+
+    fn workspace(windows: Vec<Window>) -> WorkSpace {
+        WorkSpace {
+            path: PathBuf::from("/home/me/dev/app"),
+            session_name: "dev".to_owned(),
+            profile: "default".to_owned(),
+            windows,
+            start_window: None,
+        }
+    }
+
+    #[test]
+    fn setup_workspace_creates_a_new_session() {
+        let mut recorder = Recorder::default();
+        let mut window = window(None);
+        window.panes[1].dir = Some(PathBuf::from("server"));
+        setup_workspace(workspace(vec![window]), &mut recorder).unwrap();
+        let mut lines = recorder.command_lines();
+        // the time the window was opened changes with every run
+        let created = lines.remove(
+            lines
+                .iter()
+                .position(|line| line.contains("@dmux_created"))
+                .unwrap(),
+        );
+        assert!(
+            created.starts_with("set-window-option -t dev:app @dmux_created "),
+            "{}",
+            created
+        );
+        assert_eq!(
+            &lines[..8],
+            &[
+                "new-session -d -s dev -n app -c /home/me/dev/app -x 230 -y 56",
+                "split-window -t dev:app -c /home/me/dev/app/server",
+                "split-window -t dev:app -c /home/me/dev/app",
+                "set-window-option -t dev:app @dmux_root /home/me/dev/app",
+                "set-window-option -t dev:app @dmux_profile default",
+                "select-layout -t dev:app.0 '34ed,230x56,0,0{132x56,0,0,3,97x56,133,0,222}'",
+                "send-keys -t dev:app.0 nvim Enter",
+                "send-keys -t dev:app.1 fish Enter",
+            ]
+        );
+        assert_eq!(lines[8], "select-window -t dev:app");
+        assert_eq!(lines[9], "attach-session -t dev:app");
+    }
+
+    #[test]
+    fn setup_workspace_switches_clients_inside_tmux() {
+        let mut recorder = Recorder {
+            in_tmux: true,
+            ..Recorder::default()
         };
-        assert_eq!(wp.window_name(), "some_name")
+        setup_workspace(workspace(vec![window(None)]), &mut recorder).unwrap();
+        assert_eq!(
+            recorder.command_lines().last().unwrap(),
+            "switch-client -t dev:app"
+        );
+    }
+
+    #[test]
+    fn setup_workspace_adds_windows_to_an_existing_session() {
+        let mut recorder = Recorder::with_session("dev", &["other"]);
+        setup_workspace(workspace(vec![window(Some("logs"))]), &mut recorder).unwrap();
+        assert_eq!(
+            recorder.command_lines()[0],
+            "new-window -d -t dev: -n logs -c /home/me/dev/app"
+        );
+        assert!(!recorder.command_names().contains(&"new-session"));
+        assert_eq!(
+            recorder
+                .command_names()
+                .iter()
+                .filter(|c| **c == "split-window")
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn setup_workspace_reuses_existing_windows() {
+        let mut recorder = Recorder::with_session("dev", &["app", "logs"]);
+        let mut wp = workspace(vec![window(None), window(Some("logs")), window(Some("db"))]);
+        wp.start_window = Some("logs".to_owned());
+        setup_workspace(wp, &mut recorder).unwrap();
+        // only the missing window is created and set up
+        assert_eq!(
+            recorder.command_lines()[0],
+            "new-window -d -t dev: -n db -c /home/me/dev/app"
+        );
+        assert!(recorder.command_lines()[1..]
+            .iter()
+            .filter(
+                |command| !command.starts_with("select-window") && !command.contains("-t dev:logs")
+            )
+            .all(|command| command.contains("dev:db")));
+        assert!(recorder
+            .command_lines()
+            .contains(&"select-window -t dev:logs".to_owned()));
+    }
+
+    #[test]
+    fn setup_workspace_focuses_and_zooms_panes() {
+        let mut recorder = Recorder::default();
+        let mut window = window(None);
+        window.panes[0].title = Some("editor".to_owned());
+        window.panes[1].zoom = true;
+        setup_workspace(workspace(vec![window]), &mut recorder).unwrap();
+        for command in [
+            "select-pane -t dev:app.0 -T editor",
+            "select-pane -t dev:app.1",
+            "resize-pane -t dev:app.1 -Z",
+        ] {
+            assert!(
+                recorder.command_lines().contains(&command.to_owned()),
+                "{}",
+                command
+            );
+        }
     }
 }
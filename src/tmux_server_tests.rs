@@ -0,0 +1,381 @@
+// Sets up workspaces on a private tmux server and checks what tmux ends up with.
+// Every test starts its own server on a unique socket, so they don't touch
+// the user's sessions and are skipped when tmux isn't installed.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::backend::{Backend, Recorder, TmuxBackend};
+use crate::layout::{Cell, CellKind, Layout};
+use crate::snapshot;
+use crate::tmux::{self, Pane, Window, WorkSpace};
+
+static NEXT_SERVER: AtomicUsize = AtomicUsize::new(0);
+
+struct TestServer {
+    socket_name: String,
+    // the directory workspaces are opened in
+    root: PathBuf,
+    // tmux leaves the socket behind when its server is killed
+    socket_path: Option<PathBuf>,
+}
+
+impl TestServer {
+    // A detached 200x50 server, or None when there's no tmux to run
+    fn start() -> Option<TestServer> {
+        if !TmuxBackend::default().is_installed() {
+            eprintln!("tmux isn't installed, skipping");
+            return None;
+        }
+        let socket_name = format!(
+            "dmux-test-{}-{}",
+            std::process::id(),
+            NEXT_SERVER.fetch_add(1, Ordering::SeqCst)
+        );
+        let root = std::env::temp_dir().join(&socket_name).join("app");
+        fs::create_dir_all(root.join("src")).unwrap();
+        let mut server = TestServer {
+            socket_name,
+            root,
+            socket_path: None,
+        };
+        server.tmux(&[
+            "-f",
+            "/dev/null",
+            "new-session",
+            "-d",
+            "-s",
+            "harness",
+            "-x",
+            "200",
+            "-y",
+            "50",
+        ]);
+        // sessions created by dmux are detached too, so they get this size
+        server.tmux(&["set-option", "-g", "default-size", "200x50"]);
+        server.socket_path = Some(PathBuf::from(
+            server
+                .tmux(&["display-message", "-p", "#{socket_path}"])
+                .trim(),
+        ));
+        Some(server)
+    }
+
+    fn tmux(&self, args: &[&str]) -> String {
+        let output = Command::new("tmux")
+            .arg("-L")
+            .arg(&self.socket_name)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "tmux {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn lines(&self, args: &[&str]) -> Vec<String> {
+        self.tmux(args).lines().map(String::from).collect()
+    }
+
+    fn backend(&self) -> TmuxBackend {
+        TmuxBackend::new(Some(self.socket_name.clone()), None)
+    }
+
+    fn workspace(&self, windows: Vec<Window>) -> WorkSpace {
+        WorkSpace {
+            path: self.root.clone(),
+            session_name: "dev".to_owned(),
+            profile: "default".to_owned(),
+            windows,
+            start_window: None,
+        }
+    }
+
+    fn dir(&self, dir: &str) -> String {
+        self.root.join(dir).display().to_string()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = Command::new("tmux")
+            .args(["-L", &self.socket_name, "kill-server"])
+            .output();
+        if let Some(socket_path) = &self.socket_path {
+            let _ = fs::remove_file(socket_path);
+        }
+        if let Some(dir) = self.root.parent() {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+fn window(name: &str, layout: &str, panes: Vec<Pane>) -> Window {
+    Window {
+        name: Some(name.to_owned()),
+        layout: layout.parse().unwrap(),
+        number_of_panes: panes.len() as u8,
+        panes,
+        main_pane_width: None,
+        main_pane_height: None,
+    }
+}
+
+fn pane_in(dir: &str) -> Pane {
+    Pane {
+        dir: Some(PathBuf::from(dir)),
+        ..Pane::default()
+    }
+}
+
+fn pane_sizes(cell: &Cell) -> Vec<String> {
+    match &cell.kind {
+        CellKind::Pane(_) => vec![format!("{}x{}", cell.width, cell.height)],
+        CellKind::Split(_, children) => children.iter().flat_map(pane_sizes).collect(),
+    }
+}
+
+const LAYOUT: &str = "34ed,230x56,0,0{132x56,0,0,3,97x56,133,0,222}";
+
+#[test]
+fn creates_a_session_with_its_panes_dirs_and_layout() {
+    let server = match TestServer::start() {
+        Some(server) => server,
+        None => return,
+    };
+    let workspace = server.workspace(vec![window(
+        "editor",
+        LAYOUT,
+        vec![pane_in("src"), Pane::default()],
+    )]);
+    tmux::create_workspace(&workspace, &mut server.backend()).unwrap();
+
+    let panes = server.lines(&[
+        "list-panes",
+        "-t",
+        "dev:editor",
+        "-F",
+        "#{pane_current_path} #{pane_width}x#{pane_height}",
+    ]);
+    // the session is made the size of the layout
+    let layout: Layout = LAYOUT.parse().unwrap();
+    let sizes = pane_sizes(&layout.root);
+    assert_eq!(
+        panes,
+        vec![
+            format!("{} {}", server.dir("src"), sizes[0]),
+            format!("{} {}", server.root.display(), sizes[1]),
+        ]
+    );
+}
+
+#[test]
+fn creates_every_window_and_reuses_existing_ones() {
+    let server = match TestServer::start() {
+        Some(server) => server,
+        None => return,
+    };
+    let mut workspace = server.workspace(vec![
+        window("editor", "even-horizontal", vec![Pane::default(); 3]),
+        window("logs", LAYOUT, vec![Pane::default(); 2]),
+    ]);
+    workspace.start_window = Some("logs".to_owned());
+    let mut backend = server.backend();
+    tmux::create_workspace(&workspace, &mut backend).unwrap();
+    // opening it again leaves the windows as they are
+    tmux::create_workspace(&workspace, &mut backend).unwrap();
+
+    let windows = server.lines(&[
+        "list-windows",
+        "-t",
+        "dev",
+        "-F",
+        "#{window_name} #{window_panes} #{window_active}",
+    ]);
+    assert_eq!(windows, vec!["editor 3 0", "logs 2 1"]);
+}
+
+#[test]
+fn applies_preset_layouts_with_main_pane_size() {
+    let server = match TestServer::start() {
+        Some(server) => server,
+        None => return,
+    };
+    let mut window = window("notes", "main-vertical", vec![Pane::default(); 3]);
+    window.main_pane_width = Some("120".to_owned());
+    tmux::create_workspace(&server.workspace(vec![window]), &mut server.backend()).unwrap();
+
+    let widths = server.lines(&["list-panes", "-t", "dev:notes", "-F", "#{pane_width}"]);
+    assert_eq!(widths, vec!["120", "79", "79"]);
+}
+
+#[test]
+fn batched_and_unbatched_setups_agree() {
+    let server = match TestServer::start() {
+        Some(server) => server,
+        None => return,
+    };
+    let panes = vec![pane_in("src"), Pane::from("echo one; echo two;".to_owned())];
+    let mut workspace = server.workspace(vec![window("editor", LAYOUT, panes)]);
+    let mut unbatched = server.backend();
+    unbatched.batch = false;
+    tmux::create_workspace(&workspace, &mut unbatched).unwrap();
+    workspace.session_name = "batched".to_owned();
+    tmux::create_workspace(&workspace, &mut server.backend()).unwrap();
+
+    let format = "#{pane_current_path} #{pane_width}x#{pane_height}";
+    let batched = server.lines(&["list-panes", "-t", "batched:editor", "-F", format]);
+    assert_eq!(
+        batched,
+        server.lines(&["list-panes", "-t", "dev:editor", "-F", format])
+    );
+}
+
+#[test]
+fn sends_the_whole_build_in_one_call() {
+    let server = match TestServer::start() {
+        Some(server) => server,
+        None => return,
+    };
+    let mut workspace = server.workspace(vec![
+        window("editor", LAYOUT, vec![pane_in("src"), Pane::default()]),
+        window("logs", LAYOUT, vec![Pane::default(); 2]),
+    ]);
+    let mut backend = server.backend();
+    tmux::create_workspace(&workspace, &mut backend).unwrap();
+    // has-session, then everything else
+    assert_eq!(backend.calls(), 2);
+
+    // adding to a session also asks for its windows and their size first
+    workspace
+        .windows
+        .push(window("tests", LAYOUT, vec![Pane::default(); 2]));
+    let mut backend = server.backend();
+    tmux::create_workspace(&workspace, &mut backend).unwrap();
+    assert_eq!(backend.calls(), 4);
+    let sizes = server.lines(&["list-panes", "-t", "dev:tests", "-F", "#{pane_width}"]);
+    assert_eq!(
+        sizes,
+        server.lines(&["list-panes", "-t", "dev:logs", "-F", "#{pane_width}"])
+    );
+}
+
+#[test]
+fn dry_runs_ask_the_server_what_exists() {
+    let server = match TestServer::start() {
+        Some(server) => server,
+        None => return,
+    };
+    let mut workspace = server.workspace(vec![window("editor", LAYOUT, vec![Pane::default(); 2])]);
+    workspace.session_name = "harness".to_owned();
+    let mut recorder = Recorder::dry_run(&server.backend());
+    tmux::create_workspace(&workspace, &mut recorder).unwrap();
+
+    let commands = recorder.script();
+    assert!(commands[0].contains(" new-window "), "{}", commands[0]);
+    // the layout is scaled to the harness's 200x50 windows
+    let layout: Layout = LAYOUT.parse().unwrap();
+    let scaled = layout.scale(200, 50).unwrap().to_string();
+    assert!(
+        commands.iter().any(|command| command.contains(&scaled)),
+        "{:?}",
+        commands
+    );
+    // nothing was run
+    assert_eq!(server.lines(&["list-windows", "-t", "harness"]).len(), 1);
+}
+
+#[test]
+fn tags_windows_with_their_workspace() {
+    let server = match TestServer::start() {
+        Some(server) => server,
+        None => return,
+    };
+    let workspace = server.workspace(vec![
+        window("editor", "even-horizontal", vec![Pane::default()]),
+        window("logs", "tiled", vec![Pane::default()]),
+    ]);
+    let mut backend = server.backend();
+    tmux::create_workspace(&workspace, &mut backend).unwrap();
+
+    let windows = backend.list_windows(Some("dev")).unwrap();
+    assert!(windows.iter().all(|window| {
+        window.profile.as_deref() == Some("default") && window.created.is_some()
+    }));
+    let roots: Vec<(String, Option<PathBuf>)> = windows
+        .into_iter()
+        .map(|window| (window.name, window.root))
+        .collect();
+    let root = Some(server.root.clone());
+    assert_eq!(
+        roots,
+        vec![
+            ("editor".to_owned(), root.clone()),
+            ("logs".to_owned(), root)
+        ]
+    );
+    // the harness's own session was made by hand
+    let harness = backend.list_windows(Some("harness")).unwrap();
+    assert_eq!(harness[0].root, None);
+}
+
+#[test]
+fn restores_a_saved_session() {
+    let server = match TestServer::start() {
+        Some(server) => server,
+        None => return,
+    };
+    let mut workspace = server.workspace(vec![
+        window("editor", LAYOUT, vec![pane_in("src"), Pane::default()]),
+        window("logs", "even-vertical", vec![Pane::default(); 2]),
+    ]);
+    workspace.start_window = Some("logs".to_owned());
+    let mut backend = server.backend();
+    tmux::create_workspace(&workspace, &mut backend).unwrap();
+    let format =
+        "#{window_name} #{window_active} #{pane_current_path} #{pane_width}x#{pane_height}";
+    let before = server.lines(&["list-panes", "-s", "-t", "dev", "-F", format]);
+
+    let snapshot = snapshot::snapshot("dev", &mut backend).unwrap();
+    server.tmux(&["kill-session", "-t", "dev"]);
+    tmux::create_workspace(&snapshot.to_workspace().unwrap(), &mut backend).unwrap();
+
+    assert_eq!(
+        server.lines(&["list-panes", "-s", "-t", "dev", "-F", format]),
+        before
+    );
+}
+
+#[test]
+fn restores_windows_that_share_a_name() {
+    let server = match TestServer::start() {
+        Some(server) => server,
+        None => return,
+    };
+    let workspace = server.workspace(vec![
+        window("zsh", LAYOUT, vec![pane_in("src"), Pane::default()]),
+        window("other", "even-vertical", vec![Pane::default(); 3]),
+    ]);
+    let mut backend = server.backend();
+    tmux::create_workspace(&workspace, &mut backend).unwrap();
+    server.tmux(&["rename-window", "-t", "dev:other", "zsh"]);
+
+    let snapshot = snapshot::snapshot("dev", &mut backend).unwrap();
+    server.tmux(&["kill-session", "-t", "dev"]);
+    tmux::create_workspace(&snapshot.to_workspace().unwrap(), &mut backend).unwrap();
+
+    let windows = server.lines(&[
+        "list-windows",
+        "-t",
+        "dev",
+        "-F",
+        "#{window_name} #{window_panes} #{window_active}",
+    ]);
+    assert_eq!(windows, vec!["zsh 2 1", "zsh-2 3 0"]);
+}
